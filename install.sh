@@ -228,6 +228,7 @@ User=root
 ExecStart=${EXEC_START_CMD}
 Restart=always
 RestartSec=5
+SuccessExitStatus=130 143
 StandardOutput=journal
 StandardError=journal
 
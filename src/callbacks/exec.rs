@@ -1,3 +1,6 @@
+use crate::auth::PanelAuth;
+use crate::supervisor::CancelSignal;
+use log::info;
 use miniserde::{Deserialize, Serialize, json};
 use std::process::Stdio;
 use time::OffsetDateTime;
@@ -20,39 +23,44 @@ pub struct RemoteExecCallback {
 }
 
 // 直接接收字符串而不是结构体，避免重复解析
+// 进程退出时收到取消信号，终止子进程，但仍会把结果回传给主端，避免任务一直处于执行中
 pub async fn exec_command(
     utf8_str: &str,
     callback_url: String,
-    ignore_unsafe_cert: &bool,
+    auth: &PanelAuth,
+    mut cancel: CancelSignal,
 ) -> Result<(), String> {
     let remote_exec: RemoteExec =
         json::from_str(utf8_str).map_err(|_| "无法解析 RemoteExec".to_string())?;
 
-    let exec = tokio::spawn(async move {
-        let Ok(child) = Command::new("bash")
-            .arg("-c")
-            .arg(&remote_exec.command) // 避免克隆
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        else {
-            return Err("failed to execute process".to_string());
-        };
-
-        let Ok(output) = child.wait_with_output().await else {
-            return Err("failed to get process output".to_string());
-        };
+    let Ok(child) = Command::new("bash")
+        .arg("-c")
+        .arg(&remote_exec.command) // 避免克隆
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    else {
+        return Err("failed to execute process".to_string());
+    };
 
-        let stdout_str = String::from_utf8_lossy(&output.stdout);
-        let stderr_str = String::from_utf8_lossy(&output.stderr);
+    let (status, output) = tokio::select! {
+        output = child.wait_with_output() => {
+            let Ok(output) = output else {
+                return Err("failed to get process output".to_string());
+            };
 
-        let status = output.status.code().unwrap_or(1);
+            let stdout_str = String::from_utf8_lossy(&output.stdout);
+            let stderr_str = String::from_utf8_lossy(&output.stderr);
 
-        Ok((status, format!("{stdout_str}{stderr_str}"))) // 简化字符串拼接
-    });
+            let status = output.status.code().unwrap_or(1);
 
-    let Ok(Ok((status, output))) = exec.await else {
-        return Err("failed to execute process".to_string());
+            (status, format!("{stdout_str}{stderr_str}")) // 简化字符串拼接
+        }
+        () = cancel.cancelled() => {
+            info!("远程执行任务 {} 被取消", remote_exec.task_id);
+            (130, "任务已被取消: Agent 正在退出".to_string())
+        }
     };
 
     let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
@@ -66,37 +74,9 @@ pub async fn exec_command(
     };
 
     let json_string = json::to_string(&reply);
-    #[cfg(feature = "ureq-support")]
-    {
-        use crate::utils::create_ureq_agent;
-        let agent = create_ureq_agent(*ignore_unsafe_cert);
-        if let Ok(req) = agent.post(callback_url).send(&json_string) {
-            if req.status().is_success() {
-                Ok(())
-            } else {
-                Err("server returned a error".to_string())
-            }
-        } else {
-            Err("Unable to connect server".to_string())
-        }
-    }
-
-    #[cfg(feature = "nyquest-support")]
-    {
-        use nyquest::Body;
-        use nyquest::Request;
-        let client = crate::utils::create_nyquest_client(*ignore_unsafe_cert);
-        let body = Body::text(json_string, "application/json");
-        let request = Request::post(callback_url).with_body(body);
-
-        if let Ok(res) = client.request(request) {
-            if res.status().is_successful() {
-                Ok(())
-            } else {
-                Err("server returned a error".to_string())
-            }
-        } else {
-            Err("Unable to connect server".to_string())
-        }
+    match crate::utils::post_to_panel(&callback_url, &json_string, auth) {
+        Ok(status) if (200..300).contains(&status) => Ok(()),
+        Ok(_) => Err("server returned a error".to_string()),
+        Err(_) => Err("Unable to connect server".to_string()),
     }
 }
@@ -1,7 +1,9 @@
 use crate::callbacks::exec::exec_command;
 use crate::callbacks::ping::ping_target;
 use crate::callbacks::pty::{get_pty_ws_link, handle_pty_session};
-use crate::command_parser::Args;
+use crate::liveness::Liveness;
+use crate::supervisor::ConnectionSupervisor;
+use crate::target::Target;
 use crate::utils::{ConnectionUrls, connect_ws};
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
@@ -26,8 +28,11 @@ type Reader = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
 type LockedWriter = Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>;
 
 pub async fn handle_callbacks(
-    args: &Args,
+    target: &Target,
+    terminal_entry: &str,
     connection_urls: &ConnectionUrls,
+    liveness: &Liveness,
+    supervisor: &ConnectionSupervisor,
     reader: &mut Reader,
     locked_writer: &LockedWriter,
 ) -> () {
@@ -35,12 +40,13 @@ pub async fn handle_callbacks(
         let Ok(msg) = msg else {
             continue;
         };
+        liveness.touch();
 
         let Ok(utf8) = msg.into_text() else {
             continue;
         };
 
-        info!("主端传入信息: {}", utf8.as_str());
+        info!("[{}] 主端传入信息: {}", target.name, utf8.as_str());
 
         let json: Msg = if let Ok(value) = json::from_str(utf8.as_str()) {
             value
@@ -52,17 +58,18 @@ pub async fn handle_callbacks(
 
         match json.message.as_str() {
             "exec" => {
-                if args.terminal {
-                    tokio::spawn({
+                if target.exec {
+                    supervisor.spawn_exec(|cancel| {
                         let utf8_cloned_for_exec = utf8_cloned.clone();
                         let exec_callback_url = connection_urls.exec_callback.clone();
-                        let ignore_unsafe_cert = args.ignore_unsafe_cert;
+                        let auth = connection_urls.auth.clone();
 
                         async move {
                             if let Err(e) = exec_command(
                                 &utf8_cloned_for_exec,
                                 exec_callback_url,
-                                &ignore_unsafe_cert,
+                                &auth,
+                                cancel,
                             )
                             .await
                             {
@@ -71,13 +78,13 @@ pub async fn handle_callbacks(
                         }
                     });
                 } else {
-                    error!("终端功能未启用");
+                    error!("[{}] 远程执行功能未启用", target.name);
                 }
             }
 
             "ping" => {
                 let locked_write_for_ping = locked_writer.clone();
-                tokio::spawn(async move {
+                supervisor.spawn(async move {
                     match ping_target(&utf8_cloned).await {
                         Ok(json_res) => {
                             let mut write = locked_write_for_ping.lock().await;
@@ -97,12 +104,14 @@ pub async fn handle_callbacks(
             }
 
             "terminal" => {
-                if args.terminal {
+                if target.terminal {
                     let ws_terminal_url = connection_urls.clone().ws_terminal.clone();
-                    let args = args.clone();
+                    let auth = connection_urls.auth.clone();
+                    let terminal_entry = terminal_entry.to_string();
                     let utf8_cloned = utf8_cloned.clone();
+                    let cancel = supervisor.cancel_signal();
 
-                    tokio::spawn(async move {
+                    supervisor.spawn_session(async move {
                         let ws_url = match get_pty_ws_link(&utf8_cloned, &ws_terminal_url) {
                             Ok(ws_url) => ws_url,
                             Err(e) => {
@@ -111,21 +120,21 @@ pub async fn handle_callbacks(
                             }
                         };
 
-                        let ws_stream =
-                            match connect_ws(&ws_url, args.tls, args.ignore_unsafe_cert).await {
-                                Ok(ws_stream) => ws_stream,
-                                Err(e) => {
-                                    error!("无法连接到 PTY Websocket: {e}");
-                                    return;
-                                }
-                            };
+                        let ws_stream = match connect_ws(&ws_url, &auth).await {
+                            Ok(ws_stream) => ws_stream,
+                            Err(e) => {
+                                error!("无法连接到 PTY Websocket: {e}");
+                                return;
+                            }
+                        };
 
-                        if let Err(e) = handle_pty_session(ws_stream, &args.terminal_entry).await {
+                        if let Err(e) = handle_pty_session(ws_stream, &terminal_entry, cancel).await
+                        {
                             error!("PTY Websocket 处理错误: {e}");
                         }
                     });
                 } else {
-                    error!("终端功能未启用");
+                    error!("[{}] 终端功能未启用", target.name);
                 }
             }
             _ => {}
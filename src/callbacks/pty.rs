@@ -1,13 +1,21 @@
+use crate::supervisor::CancelSignal;
 use futures::{SinkExt, StreamExt};
 use log::{error, info};
 use miniserde::{Deserialize, Serialize};
 use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
 use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
 use tokio::{sync::mpsc, task};
-use tokio_tungstenite::tungstenite::Bytes;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Bytes, Utf8Bytes};
 use tokio_tungstenite::{WebSocketStream, tungstenite::protocol::Message};
 
+/// 会话被取消时等待 Close 帧发出的时间
+const CLOSE_FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TerminalEvent {
     message: String,
@@ -18,13 +26,17 @@ pub fn get_pty_ws_link(utf8_str: &str, ws_terminal_url: &str) -> Result<String,
     let ping_event: TerminalEvent =
         miniserde::json::from_str(utf8_str).map_err(|_| "无法解析 TerminalEvent".to_string())?;
 
-    Ok(format!(
-        "{ws_terminal_url}&id={request_id}",
-        request_id = ping_event.request_id
-    ))
+    let mut url = url::Url::parse(ws_terminal_url).map_err(|e| format!("无法解析 PTY URL: {e}"))?;
+    url.query_pairs_mut()
+        .append_pair("id", &ping_event.request_id);
+    Ok(url.to_string())
 }
 
-pub async fn handle_pty_session<S>(ws_stream: WebSocketStream<S>, cmd: &str) -> Result<(), String>
+pub async fn handle_pty_session<S>(
+    ws_stream: WebSocketStream<S>,
+    cmd: &str,
+    mut cancel: CancelSignal,
+) -> Result<(), String>
 where
     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
@@ -85,9 +97,24 @@ where
         }
     });
 
-    let pty_to_ws_task = tokio::spawn(async move {
+    let mut session_cancel = cancel.clone();
+    let mut pty_to_ws_task = tokio::spawn(async move {
         let mut ws_sender = ws_sender;
-        while let Some(data) = pty_to_ws_rx.recv().await {
+        loop {
+            let data = tokio::select! {
+                data = pty_to_ws_rx.recv() => data,
+                () = session_cancel.cancelled() => {
+                    let frame = CloseFrame {
+                        code: CloseCode::Away,
+                        reason: Utf8Bytes::from_static("agent shutting down"),
+                    };
+                    let _ = ws_sender.send(Message::Close(Some(frame))).await;
+                    break;
+                }
+            };
+            let Some(data) = data else {
+                break;
+            };
             if ws_sender
                 .send(Message::Binary(Bytes::from(data)))
                 .await
@@ -99,7 +126,7 @@ where
         }
     });
 
-    let ws_to_pty_task = tokio::spawn(async move {
+    let mut ws_to_pty_task = tokio::spawn(async move {
         while let Some(result) = ws_receiver.next().await {
             match result {
                 Ok(msg) => match handle_ws_message(msg, &pty_writer) {
@@ -128,9 +155,16 @@ where
     });
 
     tokio::select! {
-        _ = pty_to_ws_task => info!("PTY -> WebSocket 任务结束。"),
-        _ = ws_to_pty_task => info!("WebSocket -> PTY 任务结束。"),
+        _ = &mut pty_to_ws_task => info!("PTY -> WebSocket 任务结束。"),
+        _ = &mut ws_to_pty_task => info!("WebSocket -> PTY 任务结束。"),
+        () = cancel.cancelled() => {
+            info!("上报连接已关闭，结束终端会话。");
+            // 给 PTY -> WebSocket 任务一点时间向主端发送 Close 帧
+            let _ = timeout(CLOSE_FRAME_TIMEOUT, &mut pty_to_ws_task).await;
+        }
     }
+    pty_to_ws_task.abort();
+    ws_to_pty_task.abort();
 
     info!("正在关闭会话，终止子进程...");
     if let Err(e) = child.kill() {
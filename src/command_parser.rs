@@ -1,32 +1,70 @@
-use clap::{Parser, ValueEnum};
+use crate::auth::Secret;
+use crate::config_file::{self, Value};
+use crate::get_info::network::interfaces::validate_pattern;
+use crate::proxy::ProxyUrl;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
 use std::fmt;
 use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static ARGV: OnceLock<Vec<OsString>> = OnceLock::new();
 
 #[derive(Parser, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 #[command(
     version,
+    subcommand_negates_reqs = true,
     long_about = "komari-monitor-rs is a third-party high-performance monitoring agent for the komari monitoring service.",
-    after_long_help = "必须设置 --http-server / --token\n--ip-provider 接受 cloudflare / ipinfo\n--log-level 接受 error, warn, info, debug, trace\n\n本 Agent 开源于 Github , 使用强力的 Rust 驱动, 爱来自 Komari"
+    after_long_help = "必须设置 --http-server 以及 --token / --token-file 之一\n--ip-provider 接受 cloudflare / ipinfo\n--log-level 接受 error, warn, info, debug, trace\n\n本 Agent 开源于 Github , 使用强力的 Rust 驱动, 爱来自 Komari"
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// 配置文件路径 (TOML)，命令行参数与环境变量优先于配置文件
     #[arg(long)]
+    #[arg(env = "KOMARI_CONFIG")]
+    pub config: Option<String>,
+
+    /// 主端 HTTP 地址，可设置多个 (逗号分隔)，第一个为主用，其余按顺序作为备用
+    #[arg(long, required = true, value_delimiter = ',')]
     #[arg(env = "HTTP_SERVER")]
-    pub http_server: String,
-    
-    #[arg(long)]
+    pub http_server: Vec<String>,
+
+    /// 主端 WebSocket 地址，按顺序与 --http-server 一一对应，未设置的由 HTTP 地址推导
+    #[arg(long, value_delimiter = ',')]
     #[arg(env = "WS_SERVER")]
-    pub ws_server: Option<String>,
-    
-    #[arg(short, long, allow_hyphen_values = true)]
-    #[arg(env = "TOKEN")]
-    pub token: String,
+    pub ws_server: Vec<String>,
+
+    /// 主端 Token，建议改用 --token-file 以免出现在进程列表中
+    #[arg(
+        short,
+        long,
+        allow_hyphen_values = true,
+        default_value = "",
+        hide_default_value = true
+    )]
+    #[arg(env = "TOKEN", hide_env_values = true)]
+    pub token: Secret,
+
+    /// 从文件读取 Token (未设置 Token 时也会尝试读取 systemd `LoadCredential` 提供的 token 凭据)
+    #[arg(long, conflicts_with = "token")]
+    #[arg(env = "TOKEN_FILE")]
+    pub token_file: Option<String>,
+
+    /// Token 发送方式，auto 模式在主端不支持 Authorization 头时回退到 query
+    #[arg(long, default_value_t = auth_mode())]
+    pub auth_mode: AuthMode,
 
     /// 公网 IP 接口
     #[arg(long, default_value_t=ip_provider())]
     pub ip_provider: IpProvider,
 
     /// 启用 Terminal (默认开启)
-    #[arg(long, default_value_t = true)]
+    #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub terminal: bool,
 
     /// 自定义 Terminal 入口
@@ -41,23 +79,419 @@ pub struct Args {
     #[arg(long, default_value_t = 1000)]
     pub realtime_info_interval: u64,
 
-    /// 启用 TLS (默认关闭)
-    #[arg(long, default_value_t = false)]
+    /// 上报每个核心的占用率与频率，以及 /proc/stat 中的 CPU 时间分布 (默认关闭，需主端支持)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub cpu_detail: bool,
+
+    /// 上报 hwmon 与热区中的温度、风扇转速与电压 (默认关闭，需主端支持)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub sensors: bool,
+
+    /// 仅上报名称匹配的传感器 (`芯片名/标签`，支持 * 与 ? 通配符)，可重复设置
+    #[arg(long, value_delimiter = ',')]
+    pub sensor_include: Vec<String>,
+
+    /// 不上报名称匹配的传感器，优先于 --sensor-include
+    #[arg(long, value_delimiter = ',')]
+    pub sensor_exclude: Vec<String>,
+
+    /// 上报显卡占用率与显存 (默认关闭，需主端支持，目前仅 amdgpu 提供)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub gpu: bool,
+
+    /// 上报块设备的读写速度、IOPS、平均等待时间与利用率 (默认关闭，需主端支持)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub disk_io: bool,
+
+    /// 统计 I/O 的块设备名称 (支持 * 与 ? 通配符)，未设置时统计除 loop、ram、zram、dm 与 md 外的整块磁盘
+    #[arg(long, value_delimiter = ',')]
+    pub disk_io_devices: Vec<String>,
+
+    /// 额外统计的磁盘，格式为 fs:<文件系统>、mount:<挂载点> 或 device:<设备> (支持通配符)，
+    /// 与内置的文件系统列表共同生效，可重复设置 (Linux 下不统计 NFS、CIFS 等网络文件系统)
+    #[arg(long, value_delimiter = ',', value_parser = DiskRule::parse)]
+    pub disk_include: Vec<DiskRule>,
+
+    /// 不统计的磁盘，格式同 --disk-include，优先于包含规则
+    #[arg(long, value_delimiter = ',', value_parser = DiskRule::parse)]
+    pub disk_exclude: Vec<DiskRule>,
+
+    /// 仅统计名称匹配的网卡，支持通配符，以 re: 开头时按正则表达式匹配，可重复设置
+    #[arg(long, value_delimiter = ',', value_parser = validate_pattern)]
+    pub net_include: Vec<String>,
+
+    /// 不统计名称匹配的网卡，格式同 --net-include，优先于包含规则，设置后替换默认列表
+    #[arg(long, value_delimiter = ',', value_parser = validate_pattern, default_values_t = net_exclude())]
+    pub net_exclude: Vec<String>,
+
+    /// 仅统计拥有默认路由的网卡，忽略 --net-include 与 --net-exclude
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub net_default_route_only: bool,
+
+    /// 上报每块网卡的速率、包数、错误数与丢包数 (默认关闭，需主端支持)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub net_interfaces: bool,
+
+    /// 上报 TCP 各状态的连接数、监听端口及其所属进程与连接最多的远程地址 (默认关闭，需主端支持，仅 Linux)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub connection_detail: bool,
+
+    /// 连接详情中上报的远程地址数量
+    #[arg(long, default_value_t = 10)]
+    pub top_talkers: usize,
+
+    /// 上报连接跟踪表的使用量与丢弃计数 (默认关闭，需主端支持，仅 Linux)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub conntrack: bool,
+
+    /// 连接跟踪表使用率达到该值 (%) 时告警，0 为只在出现丢弃时告警
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub conntrack_alert_percent: u8,
+
+    /// 上报 CPU、内存与 IO 的压力阻塞信息 (PSI) (默认关闭，需主端支持，仅 Linux 4.20+)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
+    pub pressure: bool,
+
+    /// 流量账本文件，设置后持久化记录每日与每个计费周期的流量，重启后不会清零
+    #[arg(long)]
+    pub traffic_ledger_file: Option<String>,
+
+    /// 计费周期的重置日 (1-31)，超过当月天数时为当月最后一天
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=31))]
+    pub traffic_reset_day: u8,
+
+    /// 上报的累计流量: raw 为网卡计数，cycle 为本计费周期的用量，both 为网卡计数并附加周期用量
+    #[arg(long, default_value_t = traffic_report())]
+    pub traffic_report: TrafficReport,
+
+    /// 流量历史文件，设置后按小时、天与月记录每块网卡与磁盘的用量，可通过 traffic 子命令查询
+    #[arg(long, global = true)]
+    pub traffic_history_file: Option<String>,
+
+    /// 启用 TLS (默认关闭，仅为兼容保留，wss:// 与 https:// 地址会自动启用 TLS)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub tls: bool,
 
-    /// 忽略证书验证
-    #[arg(long, default_value_t = false)]
+    /// 忽略证书验证 (不安全，建议改用 --ca-file 或 --pin-sha256)
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set)]
     pub ignore_unsafe_cert: bool,
 
+    /// 主端证书公钥 (SPKI) 的 SHA-256 指纹 (Base64)，可重复设置，命中任一即信任 (Nyquest 构建不支持)
+    #[arg(long)]
+    pub pin_sha256: Vec<String>,
+
+    /// 自定义 CA 证书文件 (PEM)，设置后仅信任该文件中的 CA (Nyquest 构建不支持)
+    #[arg(long)]
+    pub ca_file: Option<String>,
+
+    /// 客户端证书文件 (PEM)，用于双向 TLS 认证 (Nyquest 构建不支持)
+    #[arg(long)]
+    pub client_cert: Option<String>,
+
+    /// 客户端私钥文件 (PEM)，用于双向 TLS 认证 (Nyquest 构建不支持)
+    #[arg(long)]
+    pub client_key: Option<String>,
+
+    /// 代理服务器地址，支持 http / socks5 / socks5h 协议 (未设置时读取环境变量，Nyquest 构建不支持此参数)
+    #[arg(long)]
+    pub proxy: Option<ProxyUrl>,
+
+    /// 配置文件中 `[[target]]` 定义的额外主端，与上面的主端同时上报
+    #[arg(skip)]
+    pub targets: Vec<TargetArgs>,
+
     /// 设置日志等级 (反馈问题请开启 Debug 或者 Trace)
     #[arg(long, default_value_t = log_level())]
     pub log_level: LogLevel,
+
+    /// 首次重连等待时间 (ms)
+    #[arg(long, default_value_t = 1000)]
+    pub reconnect_initial_delay: u64,
+
+    /// 重连等待时间上限 (ms)
+    #[arg(long, default_value_t = 60000)]
+    pub reconnect_max_delay: u64,
+
+    /// 重连等待时间的随机抖动比例 (0.0 - 1.0)
+    #[arg(long, default_value_t = 0.5)]
+    pub reconnect_jitter: f64,
+
+    /// 连续失败多少次后进入 Degraded 状态 (0 为不启用)
+    #[arg(long, default_value_t = 5)]
+    pub degraded_threshold: u32,
+
+    /// 连续连接失败多少次后切换到下一个主端
+    #[arg(long, default_value_t = 3)]
+    pub failover_threshold: u32,
+
+    /// 使用备用主端时探测主用主端的间隔 (s，0 为不回切)
+    #[arg(long, default_value_t = 300)]
+    pub failback_interval: u64,
+
+    /// 上报连接发送 WebSocket Ping 的间隔 (s，0 为不发送)
+    #[arg(long, default_value_t = 15)]
+    pub ping_interval: u64,
+
+    /// 发送 Ping 后等待响应的时间 (s)，超时即强制重连，同时作为单次发送的超时时间
+    #[arg(long, default_value_t = 10)]
+    pub pong_timeout: u64,
+
+    /// 收到 SIGTERM / SIGINT 后等待连接关闭与远程执行结果回传的最长时间 (s)
+    #[arg(long, default_value_t = 15)]
+    pub shutdown_timeout: u64,
+
+    /// 断线期间最多缓存的 Real-Time Info 条数 (0 为不缓存)
+    #[arg(long, default_value_t = 0)]
+    pub offline_buffer_size: usize,
+
+    /// 缓存样本的最长保留时间 (s)
+    #[arg(long, default_value_t = 3600)]
+    pub offline_buffer_max_age: u64,
+
+    /// 内存缓冲区满后将旧样本溢写到该文件
+    #[arg(long)]
+    pub offline_buffer_file: Option<String>,
+
+    /// 溢写文件大小上限 (MiB)
+    #[arg(long, default_value_t = 16)]
+    pub offline_buffer_file_max_size: u64,
+}
+
+/// 额外上报的主端，Token、虚假倍率与远程控制权限独立配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetArgs {
+    pub name: String,
+    pub http_server: Vec<String>,
+    pub ws_server: Vec<String>,
+    pub token: Secret,
+    pub auth_mode: AuthMode,
+    pub fake: f64,
+    pub terminal: bool,
+    pub exec: bool,
+}
+
+impl TargetArgs {
+    fn from_table(index: usize, table: &config_file::Table) -> Result<Self, String> {
+        let mut target = Self {
+            name: format!("target-{}", index + 1),
+            http_server: Vec::new(),
+            ws_server: Vec::new(),
+            token: Secret::default(),
+            auth_mode: AuthMode::Query,
+            fake: 1.0,
+            terminal: false,
+            exec: false,
+        };
+        let mut token_file = None;
+        let mut exec = None;
+
+        for (key, value) in table {
+            let invalid = || format!("[[target]] #{} 中的选项 {key} 类型错误", index + 1);
+            match key.replace('-', "_").as_str() {
+                "name" => target.name = value.as_str().ok_or_else(invalid)?.to_string(),
+                "http_server" => target.http_server = value.as_str_list().ok_or_else(invalid)?,
+                "ws_server" => target.ws_server = value.as_str_list().ok_or_else(invalid)?,
+                "token" => {
+                    target.token = Secret::from(value.as_str().ok_or_else(invalid)?.to_string());
+                }
+                "token_file" => token_file = Some(value.as_str().ok_or_else(invalid)?.to_string()),
+                "auth_mode" => {
+                    target.auth_mode =
+                        AuthMode::from_str(value.as_str().ok_or_else(invalid)?, true)?;
+                }
+                "fake" => target.fake = value.as_f64().ok_or_else(invalid)?,
+                "terminal" => target.terminal = value.as_bool().ok_or_else(invalid)?,
+                "exec" => exec = Some(value.as_bool().ok_or_else(invalid)?),
+                _ => {
+                    return Err(format!("[[target]] #{} 中存在未知的选项: {key}", index + 1));
+                }
+            }
+        }
+
+        // 未单独设置时，远程执行与 Terminal 保持一致
+        target.exec = exec.unwrap_or(target.terminal);
+
+        if target.http_server.is_empty() {
+            return Err(format!("[[target]] {} 缺少 http_server", target.name));
+        }
+        if target.token.is_empty() {
+            let Some(path) = token_file else {
+                return Err(format!(
+                    "[[target]] {} 必须设置 token 或 token_file",
+                    target.name
+                ));
+            };
+            target.token = Secret::from(
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("无法读取 Token 文件 {path}: {e}"))?
+                    .trim()
+                    .to_string(),
+            );
+        }
+
+        Ok(target)
+    }
+}
+
+/// 读取配置文件中的 `[[target]]` 段
+fn load_targets(path: &str) -> Result<Vec<TargetArgs>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("无法读取配置文件 {path}: {e}"))?;
+    let table =
+        config_file::parse(&content).map_err(|e| format!("配置文件 {path} 解析失败: {e}"))?;
+
+    let Some((_, value)) = table.iter().find(|(key, _)| key == "target") else {
+        return Ok(Vec::new());
+    };
+    let Value::Array(items) = value else {
+        return Err(format!(
+            "配置文件 {path} 中的 target 必须为 [[target]] 数组表"
+        ));
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| match item {
+            Value::Table(table) => TargetArgs::from_table(index, table),
+            _ => Err(format!(
+                "配置文件 {path} 中的 target 必须为 [[target]] 数组表"
+            )),
+        })
+        .collect()
 }
 
 fn terminal_entry() -> String {
     "default".to_string()
 }
 
+fn auth_mode() -> AuthMode {
+    AuthMode::Query
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+pub enum AuthMode {
+    /// 通过 URL 查询参数发送 Token
+    Query,
+    /// 通过 Authorization: Bearer 头发送 Token
+    Header,
+    /// 先使用 Header，主端在 Header 鉴权成功前以 401 / 403 拒绝时该地址回退到 Query
+    Auto,
+}
+
+impl fmt::Display for AuthMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthMode::Query => write!(f, "query"),
+            AuthMode::Header => write!(f, "header"),
+            AuthMode::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// 按文件系统类型、挂载点或设备匹配磁盘的规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiskRule {
+    FileSystem(String),
+    MountPoint(String),
+    Device(String),
+}
+
+impl DiskRule {
+    fn parse(rule: &str) -> Result<Self, String> {
+        match rule.split_once(':') {
+            Some(("fs", pattern)) if !pattern.is_empty() => {
+                Ok(Self::FileSystem(pattern.to_string()))
+            }
+            Some(("mount", pattern)) if !pattern.is_empty() => {
+                Ok(Self::MountPoint(pattern.to_string()))
+            }
+            Some(("device", pattern)) if !pattern.is_empty() => {
+                Ok(Self::Device(pattern.to_string()))
+            }
+            _ => Err(format!(
+                "无效的磁盘规则 {rule}，格式应为 fs:<文件系统>、mount:<挂载点> 或 device:<设备>"
+            )),
+        }
+    }
+}
+
+/// 默认排除回环与容器、虚拟机、隧道的虚拟网卡，统计物理网卡与普通网桥
+fn net_exclude() -> Vec<String> {
+    ["lo", "veth*", "docker*", "br-*", "virbr*", "tun*", "tap*"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// 查询本地流量历史 (需设置 --traffic-history-file)
+    Traffic(TrafficQuery),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct TrafficQuery {
+    /// 网卡名称，未指定网卡与磁盘时显示所有网卡的合计
+    #[arg(short, long, conflicts_with = "disk")]
+    pub interface: Option<String>,
+
+    /// 磁盘设备名称
+    #[arg(short, long)]
+    pub disk: Option<String>,
+
+    /// 统计粒度
+    #[arg(short, long, default_value_t = HistoryPeriod::Daily)]
+    pub period: HistoryPeriod,
+
+    /// 只显示最近的 N 条记录，0 为全部
+    #[arg(short = 'n', long, default_value_t = 0)]
+    pub limit: usize,
+
+    /// 以 JSON 格式输出
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+pub enum HistoryPeriod {
+    Hourly,
+    Daily,
+    Monthly,
+}
+
+impl fmt::Display for HistoryPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryPeriod::Hourly => write!(f, "hourly"),
+            HistoryPeriod::Daily => write!(f, "daily"),
+            HistoryPeriod::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+fn traffic_report() -> TrafficReport {
+    TrafficReport::Raw
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
+pub enum TrafficReport {
+    /// 网卡自开机以来的累计计数
+    Raw,
+    /// 以本计费周期的用量替换累计流量 (需设置 --traffic-ledger-file)
+    Cycle,
+    /// 保留网卡计数，并附加本计费周期与当天的用量 (需设置 --traffic-ledger-file)
+    Both,
+}
+
+impl fmt::Display for TrafficReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrafficReport::Raw => write!(f, "raw"),
+            TrafficReport::Cycle => write!(f, "cycle"),
+            TrafficReport::Both => write!(f, "both"),
+        }
+    }
+}
+
 fn ip_provider() -> IpProvider {
     IpProvider::Ipinfo
 }
@@ -104,14 +538,66 @@ impl fmt::Display for LogLevel {
     }
 }
 
+impl LogLevel {
+    pub fn to_level_filter(&self) -> log::LevelFilter {
+        match self {
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
 impl Args {
     pub fn par() -> Self {
-        let mut args = Self::parse();
+        let argv: Vec<OsString> = std::env::args_os().collect();
+        let merged = merge_config_file(&argv).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        let _ = ARGV.set(argv);
+
+        let args = Self::parse_from(merged).finish().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
         unsafe {
             crate::get_info::network::DURATION = args.realtime_info_interval as f64;
         }
-        if args.terminal_entry == "default" {
-            args.terminal_entry = {
+        args
+    }
+
+    /// 使用启动时的命令行参数与环境变量重新读取配置文件
+    pub fn reload() -> Result<Self, String> {
+        let argv = ARGV.get().ok_or_else(|| "参数尚未初始化".to_string())?;
+        let merged = merge_config_file(argv)?;
+        Self::try_parse_from(merged)
+            .map_err(|e| e.to_string())?
+            .finish()
+    }
+
+    /// 应用可以在运行时直接生效的设置，仅应在主循环中调用
+    pub fn apply_runtime_settings(&self) {
+        unsafe {
+            crate::get_info::network::DURATION = self.realtime_info_interval as f64;
+        }
+        log::set_max_level(self.log_level.to_level_filter());
+    }
+
+    fn finish(mut self) -> Result<Self, String> {
+        // 子命令只在本地运行，不需要连接主端
+        if self.command.is_some() {
+            return Ok(self);
+        }
+        self.token = self.resolve_token()?;
+        if let Some(path) = &self.config {
+            self.targets = load_targets(path)?;
+        }
+
+        if self.terminal_entry == "default" {
+            self.terminal_entry = {
                 if cfg!(windows) {
                     "cmd.exe".to_string()
                 } else if fs::metadata("/bin/bash").is_ok() {
@@ -121,6 +607,241 @@ impl Args {
                 }
             };
         }
-        args
+        Ok(self)
+    }
+
+    /// 按 --token / --token-file / systemd 凭据的顺序获取 Token
+    fn resolve_token(&self) -> Result<Secret, String> {
+        if !self.token.is_empty() {
+            return Ok(self.token.clone());
+        }
+
+        let path = if let Some(path) = &self.token_file {
+            PathBuf::from(path)
+        } else if let Some(dir) = std::env::var_os("CREDENTIALS_DIRECTORY") {
+            PathBuf::from(dir).join("token")
+        } else {
+            return Err("必须设置 --token 或 --token-file".to_string());
+        };
+
+        let token = fs::read_to_string(&path)
+            .map_err(|e| format!("无法读取 Token 文件 {}: {e}", path.display()))?;
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(format!("Token 文件 {} 为空", path.display()));
+        }
+        Ok(Secret::from(token.to_string()))
+    }
+}
+
+/// 将配置文件中的选项转换为命令行参数，插入到原始参数之前
+///
+/// 已经由命令行或环境变量设置的选项会被跳过，从而保证 命令行 > 环境变量 > 配置文件 > 默认值
+fn merge_config_file(argv: &[OsString]) -> Result<Vec<OsString>, String> {
+    let command = Args::command().ignore_errors(true);
+    let Ok(matches) = command.clone().try_get_matches_from(argv) else {
+        return Ok(argv.to_vec());
+    };
+    let Some(path) = matches.get_one::<String>("config") else {
+        return Ok(argv.to_vec());
+    };
+
+    let content = fs::read_to_string(path).map_err(|e| format!("无法读取配置文件 {path}: {e}"))?;
+    let table =
+        config_file::parse(&content).map_err(|e| format!("配置文件 {path} 解析失败: {e}"))?;
+
+    let mut merged: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    for (key, value) in &table {
+        // 表与数组表留给独立的配置段使用
+        if matches!(value, Value::Table(_))
+            || matches!(value, Value::Array(items) if items.iter().any(|v| matches!(v, Value::Table(_))))
+        {
+            continue;
+        }
+
+        let id = key.replace('-', "_");
+        if id == "config" {
+            continue;
+        }
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str())
+        else {
+            return Err(format!("配置文件 {path} 中存在未知的选项: {key}"));
+        };
+        let Some(long) = arg.get_long() else {
+            return Err(format!("配置文件 {path} 中存在未知的选项: {key}"));
+        };
+
+        // 该选项或与之冲突的选项已由命令行或环境变量设置
+        let explicitly_set = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        if explicitly_set(&id)
+            || command.get_arguments().any(|other| {
+                explicitly_set(other.get_id().as_str())
+                    && (command.get_arg_conflicts_with(arg).contains(&other)
+                        || command.get_arg_conflicts_with(other).contains(&arg))
+            })
+        {
+            continue;
+        }
+
+        let values = match value {
+            Value::Array(items) => items.clone(),
+            other => vec![other.clone()],
+        };
+        for value in values {
+            merged.push(format!("--{long}={value}").into());
+        }
+    }
+    merged.extend(argv.iter().skip(1).cloned());
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_with_config(config: &str, extra: &[&str]) -> Result<Args, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("komari.toml");
+        fs::write(&path, config).unwrap();
+
+        let mut argv: Vec<OsString> =
+            vec!["komari-monitor-rs".into(), "--config".into(), path.into()];
+        argv.extend(extra.iter().map(OsString::from));
+        let merged = merge_config_file(&argv)?;
+        Args::try_parse_from(merged).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn config_file_fills_unset_options() {
+        let args = parse_with_config(
+            "http_server = \"http://panel\"\ntoken = \"file\"\nfake = 2.5\nrealtime-info-interval = 500\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(args.http_server, ["http://panel"]);
+        assert_eq!(args.token.expose(), "file");
+        assert!((args.fake - 2.5).abs() < f64::EPSILON);
+        assert_eq!(args.realtime_info_interval, 500);
+    }
+
+    #[test]
+    fn command_line_overrides_config_file() {
+        let args = parse_with_config(
+            "http_server = \"http://panel\"\ntoken = \"file\"\nfake = 2.5\nterminal = false\n",
+            &["--fake", "3", "--terminal", "-t", "cli"],
+        )
+        .unwrap();
+        assert!((args.fake - 3.0).abs() < f64::EPSILON);
+        assert!(args.terminal);
+        assert_eq!(args.token.expose(), "cli");
+    }
+
+    #[test]
+    fn conflicting_command_line_option_skips_config_value() {
+        let args = parse_with_config(
+            "http_server = \"http://panel\"\ntoken = \"file\"\n",
+            &["--token-file", "/run/token"],
+        )
+        .unwrap();
+        assert!(args.token.is_empty());
+        assert_eq!(args.token_file.as_deref(), Some("/run/token"));
+    }
+
+    #[test]
+    fn command_line_array_replaces_config_array() {
+        let args = parse_with_config(
+            "http_server = [\"http://a\", \"http://b\"]\ntoken = \"t\"\n",
+            &["--http-server", "http://c"],
+        )
+        .unwrap();
+        assert_eq!(args.http_server, ["http://c"]);
+    }
+
+    #[test]
+    fn config_keys_accept_dashes_and_ignore_config_key() {
+        let args = parse_with_config(
+            "http-server = \"http://panel\"\ntoken = \"t\"\nconfig = \"/elsewhere.toml\"\nlog_level = \"debug\"\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(args.http_server, ["http://panel"]);
+        assert_ne!(args.config.as_deref(), Some("/elsewhere.toml"));
+        assert_eq!(args.log_level.to_level_filter(), log::LevelFilter::Debug);
+    }
+
+    #[test]
+    fn target_sections_are_read_with_their_own_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("komari.toml");
+        fs::write(
+            &path,
+            "fake = 3\n[[target]]\nhttp_server = \"http://b\"\ntoken = \"b\"\n[[target]]\nname = \"c\"\nhttp_server = \"http://c\"\ntoken = \"c\"\nfake = 1.5\n",
+        )
+        .unwrap();
+        let targets = load_targets(path.to_str().unwrap()).unwrap();
+        assert_eq!(targets[0].name, "target-1");
+        assert_eq!(targets[0].fake.to_bits(), 1.0f64.to_bits());
+        assert_eq!(targets[1].name, "c");
+        assert_eq!(targets[1].fake.to_bits(), 1.5f64.to_bits());
+    }
+
+    #[test]
+    fn config_arrays_expand_to_repeated_options() {
+        let args = parse_with_config(
+            "http_server = [\n  \"http://a\",\n  \"http://b\",\n]\ntoken = \"t\"\ndisk_include = [\"fs:nfs\", \"mount:/data\"]\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(args.http_server, ["http://a", "http://b"]);
+        assert_eq!(
+            args.disk_include,
+            [
+                DiskRule::FileSystem("nfs".to_string()),
+                DiskRule::MountPoint("/data".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn config_tables_are_left_to_their_own_sections() {
+        let args = parse_with_config(
+            "http_server = \"http://panel\"\ntoken = \"t\"\n[[target]]\nname = \"b\"\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(args.http_server, ["http://panel"]);
+    }
+
+    #[test]
+    fn unknown_config_option_is_rejected() {
+        let error = parse_with_config("http_server = \"http://panel\"\nno_such_option = 1\n", &[])
+            .unwrap_err();
+        assert!(error.contains("no_such_option"));
+    }
+
+    #[test]
+    fn argv_without_config_is_unchanged() {
+        let argv: Vec<OsString> = ["komari-monitor-rs", "--fake", "2"]
+            .map(OsString::from)
+            .to_vec();
+        assert_eq!(merge_config_file(&argv).unwrap(), argv);
+    }
+
+    #[test]
+    fn disk_rule_requires_known_prefix_and_pattern() {
+        assert_eq!(
+            DiskRule::parse("device:sd*"),
+            Ok(DiskRule::Device("sd*".to_string()))
+        );
+        assert!(DiskRule::parse("fs:").is_err());
+        assert!(DiskRule::parse("nfs").is_err());
     }
 }
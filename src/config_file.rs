@@ -0,0 +1,277 @@
+use crate::command_parser::{Args, TargetArgs};
+use log::{error, info, warn};
+use std::fmt;
+use tokio::sync::watch;
+
+/// 配置文件中的值，日期时间按字符串处理
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+pub type Table = Vec<(String, Value)>;
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(v) => Some(*v),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// 字符串或字符串数组
+    pub fn as_str_list(&self) -> Option<Vec<String>> {
+        match self {
+            Value::String(s) => Some(vec![s.clone()]),
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(ToString::to_string))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{s}"),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Float(v) => write!(f, "{v}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+            Value::Array(_) => write!(f, "[array]"),
+            Value::Table(_) => write!(f, "[table]"),
+        }
+    }
+}
+
+/// 解析 TOML 配置文件
+pub fn parse(content: &str) -> Result<Table, String> {
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+    Ok(convert_table(table))
+}
+
+fn convert_table(table: toml::Table) -> Table {
+    table
+        .into_iter()
+        .map(|(key, value)| (key, convert_value(value)))
+        .collect()
+}
+
+fn convert_value(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Integer(i),
+        toml::Value::Float(f) => Value::Float(f),
+        toml::Value::Boolean(b) => Value::Boolean(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(convert_value).collect()),
+        toml::Value::Table(table) => Value::Table(convert_table(table)),
+    }
+}
+
+/// 收到 SIGHUP 时重新读取配置文件，并通过 watch 通道下发新的参数
+#[cfg(unix)]
+pub async fn reload_on_sighup(sender: watch::Sender<Args>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+        error!("无法监听 SIGHUP 信号，配置热重载不可用");
+        return;
+    };
+
+    while hangup.recv().await.is_some() {
+        info!("收到 SIGHUP，重新读取配置文件");
+        match Args::reload() {
+            Ok(args) => {
+                warn_restart_required(&sender.borrow(), &args);
+                sender.send_replace(args);
+            }
+            Err(e) => error!("重新读取配置失败，继续使用当前配置: {e}"),
+        }
+    }
+}
+
+/// 连接、证书与磁盘统计范围相关的选项只在启动时读取，修改后需要重启才能生效
+///
+/// 虚假倍率 (包括各主端配置段中的) 可以直接生效，不在此列
+#[cfg(unix)]
+fn warn_restart_required(old: &Args, new: &Args) {
+    let changed = [
+        ("http_server", old.http_server != new.http_server),
+        ("ws_server", old.ws_server != new.ws_server),
+        ("token", old.token != new.token),
+        ("auth_mode", old.auth_mode != new.auth_mode),
+        ("terminal", old.terminal != new.terminal),
+        (
+            "target",
+            !same_targets_except_fake(&old.targets, &new.targets),
+        ),
+        ("proxy", old.proxy != new.proxy),
+        ("disk_include", old.disk_include != new.disk_include),
+        ("disk_exclude", old.disk_exclude != new.disk_exclude),
+        (
+            "traffic_ledger_file",
+            old.traffic_ledger_file != new.traffic_ledger_file,
+        ),
+        (
+            "traffic_history_file",
+            old.traffic_history_file != new.traffic_history_file,
+        ),
+        (
+            "ignore_unsafe_cert",
+            old.ignore_unsafe_cert != new.ignore_unsafe_cert,
+        ),
+        ("pin_sha256", old.pin_sha256 != new.pin_sha256),
+        ("ca_file", old.ca_file != new.ca_file),
+        ("client_cert", old.client_cert != new.client_cert),
+        ("client_key", old.client_key != new.client_key),
+        (
+            "offline_buffer_size",
+            old.offline_buffer_size != new.offline_buffer_size,
+        ),
+        (
+            "offline_buffer_file",
+            old.offline_buffer_file != new.offline_buffer_file,
+        ),
+    ];
+
+    for (name, _) in changed.iter().filter(|(_, changed)| *changed) {
+        warn!("选项 {name} 已修改，需要重启后才能生效");
+    }
+}
+
+/// 比较主端配置段，忽略可以直接生效的虚假倍率
+#[cfg(unix)]
+fn same_targets_except_fake(old: &[TargetArgs], new: &[TargetArgs]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|(old, new)| {
+            TargetArgs {
+                fake: new.fake,
+                ..old.clone()
+            } == *new
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_multi_line_arrays_and_strings() {
+        let table = parse(
+            r#"
+            disk_include = [
+                "fs:nfs",
+                "mount:/data/*", # 数据盘
+            ]
+            terminal_entry = """
+/bin/sh"""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            table[0].1.as_str_list().unwrap(),
+            ["fs:nfs", "mount:/data/*"]
+        );
+        assert_eq!(table[1].1.as_str(), Some("/bin/sh"));
+    }
+
+    #[test]
+    fn parses_array_of_tables_and_inline_tables() {
+        let table = parse(
+            r#"
+            token = "t"
+
+            [[target]]
+            name = "a"
+            http_server = ["https://a.example.com", "https://b.example.com"]
+
+            [[target]]
+            name = "b"
+            extra = { terminal = true }
+            "#,
+        )
+        .unwrap();
+
+        let Value::Array(targets) = &table[1].1 else {
+            panic!("target 应为数组表");
+        };
+        assert_eq!(targets.len(), 2);
+        let Value::Table(second) = &targets[1] else {
+            panic!("target 元素应为表");
+        };
+        assert_eq!(
+            second[0],
+            ("name".to_string(), Value::String("b".to_string()))
+        );
+        assert_eq!(
+            second[1].1,
+            Value::Table(vec![("terminal".to_string(), Value::Boolean(true))])
+        );
+    }
+
+    #[cfg(unix)]
+    fn target(name: &str, fake: f64) -> TargetArgs {
+        TargetArgs {
+            name: name.to_string(),
+            http_server: vec![format!("http://{name}")],
+            ws_server: Vec::new(),
+            token: "t".to_string().into(),
+            auth_mode: crate::command_parser::AuthMode::Query,
+            fake,
+            terminal: false,
+            exec: false,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fake_changes_in_target_sections_apply_without_restart() {
+        let old = [target("a", 1.0), target("b", 1.0)];
+        assert!(same_targets_except_fake(
+            &old,
+            &[target("a", 2.0), target("b", 1.5)]
+        ));
+        assert!(!same_targets_except_fake(
+            &old,
+            &[target("a", 2.0), target("c", 1.0)]
+        ));
+        assert!(!same_targets_except_fake(&old, &[target("a", 1.0)]));
+    }
+
+    #[test]
+    fn str_list_accepts_single_string() {
+        assert_eq!(
+            Value::String("a".to_string()).as_str_list(),
+            Some(vec!["a".to_string()])
+        );
+        assert_eq!(
+            Value::Array(vec![Value::String("a".to_string()), Value::Integer(1)]).as_str_list(),
+            None
+        );
+    }
+}
@@ -1,22 +1,26 @@
+use crate::auth::PanelAuth;
 use crate::command_parser::IpProvider;
 
 use crate::get_info::cpu::{arch, cpu_info_without_usage, realtime_cpu};
+use crate::get_info::gpu::gpu_name;
 use crate::get_info::ip::ip;
 use crate::get_info::load::realtime_load;
 use crate::get_info::mem::{mem_info_without_usage, realtime_disk, realtime_mem, realtime_swap};
-use crate::get_info::network::{realtime_connections, realtime_network};
+use crate::get_info::network::realtime_network;
 use crate::get_info::os::os;
 use crate::get_info::{realtime_process, realtime_uptime};
 use log::{debug, error, info};
+use miniserde::ser::{self, Fragment};
 use miniserde::{Deserialize, Serialize};
-use sysinfo::{Disks, Networks};
+use std::borrow::Cow;
+use sysinfo::{Disks, NetworkData};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BasicInfo {
     pub arch: String,
     pub cpu_cores: u64,
     pub cpu_name: String,
-    pub gpu_name: String, // 暂不支持
+    pub gpu_name: String,
 
     pub disk_total: u64,
     pub swap_total: u64,
@@ -32,24 +36,19 @@ pub struct BasicInfo {
 }
 
 impl BasicInfo {
-    pub async fn build(sysinfo_sys: &sysinfo::System, fake: f64, ip_provider: &IpProvider) -> Self {
+    pub async fn build(sysinfo_sys: &sysinfo::System, ip_provider: &IpProvider) -> Self {
         let cpu = cpu_info_without_usage(sysinfo_sys);
         let mem_disk = mem_info_without_usage(sysinfo_sys);
         let (ip, os) = tokio::join!(ip(ip_provider), os());
 
-        let fake_cpu_cores = (f64::from(cpu.cores) * fake) as u64;
-        let fake_disk_total = (mem_disk.disk as f64 * fake) as u64;
-        let fake_swap_total = (mem_disk.swap as f64 * fake) as u64;
-        let fake_mem_total = (mem_disk.mem as f64 * fake) as u64;
-
         let basic_info = Self {
             arch: arch(),
-            cpu_cores: fake_cpu_cores,
+            cpu_cores: u64::from(cpu.cores),
             cpu_name: cpu.name,
-            gpu_name: String::new(),
-            disk_total: fake_disk_total,
-            swap_total: fake_swap_total,
-            mem_total: fake_mem_total,
+            gpu_name: gpu_name(),
+            disk_total: mem_disk.disk,
+            swap_total: mem_disk.swap,
+            mem_total: mem_disk.mem,
             ipv4: ip.ipv4.map(|ip| ip.to_string()),
             ipv6: ip.ipv6.map(|ip| ip.to_string()),
             os: os.os,
@@ -63,52 +62,24 @@ impl BasicInfo {
         basic_info
     }
 
-    pub fn push(&self, basic_info_url: String, ignore_unsafe_cert: bool) {
-        let json_string = miniserde::json::to_string(self);
-        #[cfg(feature = "ureq-support")]
-        {
-            use crate::utils::create_ureq_agent;
-            let agent = create_ureq_agent(ignore_unsafe_cert);
-            let resp = agent
-                .post(basic_info_url)
-                .header("User-Agent", "curl/11.45.14-rs")
-                .send(&json_string);
-
-            let resp = match resp {
-                Ok(resp) => resp,
-                Err(e) => {
-                    error!("推送 Basic Info 错误: {e}");
-                    return;
-                }
-            };
-
-            if resp.status().is_success() {
-                info!("推送 Basic Info 成功");
-            } else {
-                error!("推送 Basic Info 失败，HTTP 状态码: {}", resp.status());
-            }
+    /// 按虚假倍率缩放后的副本
+    pub fn with_fake(&self, fake: f64) -> Self {
+        Self {
+            cpu_cores: (self.cpu_cores as f64 * fake) as u64,
+            disk_total: (self.disk_total as f64 * fake) as u64,
+            swap_total: (self.swap_total as f64 * fake) as u64,
+            mem_total: (self.mem_total as f64 * fake) as u64,
+            ..self.clone()
         }
-        #[cfg(feature = "nyquest-support")]
-        {
-            use nyquest::Body;
-            use nyquest::Request;
-            let client = crate::utils::create_nyquest_client(ignore_unsafe_cert);
-            let body = Body::text(json_string, "application/json");
-            let resp = client.request(Request::post(basic_info_url).with_body(body));
-
-            let resp = match resp {
-                Ok(resp) => resp,
-                Err(e) => {
-                    error!("推送 Basic Info 错误: {e}");
-                    return;
-                }
-            };
+    }
+
+    pub fn push(&self, basic_info_url: &str, auth: &PanelAuth) {
+        let json_string = miniserde::json::to_string(self);
 
-            if resp.status().is_successful() {
-                info!("推送 Basic Info 成功");
-            } else {
-                error!("推送 Basic Info 失败，HTTP 状态码: {}", resp.status());
-            }
+        match crate::utils::post_to_panel(basic_info_url, &json_string, auth) {
+            Ok(status) if (200..300).contains(&status) => info!("推送 Basic Info 成功"),
+            Ok(status) => error!("推送 Basic Info 失败，HTTP 状态码: {status}"),
+            Err(e) => error!("推送 Basic Info 错误: {e}"),
         }
     }
 }
@@ -118,6 +89,62 @@ pub struct Cpu {
     pub usage: f64,
 }
 
+/// 每个核心的占用率与频率，以及 CPU 时间分布，由 `--cpu-detail` 启用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CpuDetail {
+    pub cores: Vec<CpuCore>,
+    pub times: Option<CpuTimes>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CpuCore {
+    pub usage: f64,
+    /// 当前频率 (MHz)
+    pub frequency: u64,
+    /// 硬件支持的最低频率 (MHz)，无法获取时为 null
+    pub min_frequency: Option<u64>,
+    /// 硬件支持的最高频率 (MHz)，无法获取时为 null
+    pub max_frequency: Option<u64>,
+}
+
+/// 两次采集之间各类 CPU 时间所占的百分比 (来自 /proc/stat)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CpuTimes {
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+}
+
+/// 温度 (°C)、风扇转速 (RPM) 与电压 (V)，由 `--sensors` 启用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Sensors {
+    pub temperatures: Vec<SensorReading>,
+    pub fans: Vec<SensorReading>,
+    pub voltages: Vec<SensorReading>,
+    /// CPU 封装温度的最大值，未找到对应传感器时为 null
+    pub cpu_package_temperature: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SensorReading {
+    pub name: String,
+    pub value: f64,
+}
+
+/// 显卡占用率 (%) 与显存 (Byte)，sysfs 未提供时为 null，由 `--gpu` 启用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Gpu {
+    pub name: String,
+    pub usage: Option<f64>,
+    pub mem_used: Option<u64>,
+    pub mem_total: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Ram {
     pub used: u64,
@@ -128,9 +155,43 @@ pub struct Swap {
     pub used: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Disk {
     pub used: u64,
+    /// 所有块设备的 I/O 合计，由 `--disk-io` 启用
+    pub io: Option<DiskIo>,
+}
+
+// 手动实现 Serialize，未启用 I/O 统计时保持原有格式
+impl Serialize for Disk {
+    fn begin(&self) -> Fragment<'_> {
+        let mut fields: Vec<(&'static str, &dyn Serialize)> = vec![("used", &self.used)];
+        if let Some(io) = &self.io {
+            fields.push(("io", io));
+        }
+        Fragment::Map(Box::new(FieldStream(fields.into_iter())))
+    }
+}
+
+/// 每秒读写字节数与 IOPS
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiskIo {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_iops: f64,
+    pub write_iops: f64,
+}
+
+/// 单个块设备的 I/O 统计，平均等待时间单位为 ms，利用率为 %
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiskDeviceIo {
+    pub name: String,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_iops: f64,
+    pub write_iops: f64,
+    pub await_ms: f64,
+    pub utilization: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -140,6 +201,30 @@ pub struct Load {
     pub load15: f64,
 }
 
+/// Linux 压力阻塞信息 (PSI)，由 `--pressure` 启用，内核不支持的资源为空
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pressure {
+    pub cpu: Option<PressureResource>,
+    pub memory: Option<PressureResource>,
+    pub io: Option<PressureResource>,
+}
+
+/// some 为至少一个任务阻塞的时间占比，full 为所有非空闲任务同时阻塞的时间占比
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PressureResource {
+    pub some: PressureStall,
+    pub full: Option<PressureStall>,
+}
+
+/// 最近 10s、60s、300s 的阻塞时间占比 (%) 与累计阻塞时间 (μs)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PressureStall {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    pub total: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Network {
     pub up: u64,
@@ -151,13 +236,97 @@ pub struct Network {
     pub total_down: u64,
 }
 
+/// 单块网卡的速率 (Byte/s、包/s) 与累计流量、错误数与丢包数，由 `--net-interfaces` 启用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub up: u64,
+    pub down: u64,
+    pub total_up: u64,
+    pub total_down: u64,
+    pub packets_up: u64,
+    pub packets_down: u64,
+    pub errors_up: u64,
+    pub errors_down: u64,
+    pub drops_up: Option<u64>,
+    pub drops_down: Option<u64>,
+}
+
+/// 本计费周期与当天的流量 (Byte)，周期起始日期为 YYYY-MM-DD
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Traffic {
+    pub cycle_start: String,
+    pub cycle_up: u64,
+    pub cycle_down: u64,
+    pub today_up: u64,
+    pub today_down: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Connections {
     pub tcp: u64,
     pub udp: u64,
 }
 
+/// TCP 各状态的连接数、监听中的端口与连接数最多的远程地址，由 `--connection-detail` 启用
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConnectionDetail {
+    pub tcp_states: TcpStates,
+    pub listening: Vec<ListeningSocket>,
+    pub top_talkers: Vec<TopTalker>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TcpStates {
+    pub established: u64,
+    pub syn_sent: u64,
+    pub syn_recv: u64,
+    pub fin_wait1: u64,
+    pub fin_wait2: u64,
+    pub time_wait: u64,
+    pub close: u64,
+    pub close_wait: u64,
+    pub last_ack: u64,
+    pub listen: u64,
+    pub closing: u64,
+}
+
+/// 监听中的 TCP 端口与未连接的 UDP 端口，无法确定所属进程时 pid 与 process 为空
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListeningSocket {
+    pub protocol: String,
+    pub address: String,
+    pub port: u16,
+    pub inode: u32,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+/// 单个远程地址的 TCP 连接数与速率 (Byte/s)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TopTalker {
+    pub address: String,
+    pub connections: u64,
+    pub up: u64,
+    pub down: u64,
+}
+
+/// 连接跟踪表的使用量与各 CPU 合计的累计丢弃计数，由 `--conntrack` 启用
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Conntrack {
+    pub count: u64,
+    pub max: u64,
+    /// 使用率 (%)
+    pub usage: f64,
+    pub drop: u64,
+    pub insert_failed: u64,
+    pub early_drop: u64,
+    pub invalid: u64,
+    /// 使用率达到 `--conntrack-alert-percent` 或本周期出现新的丢弃
+    pub alert: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct RealTimeInfo {
     pub cpu: Cpu,
     pub ram: Ram,
@@ -169,77 +338,232 @@ pub struct RealTimeInfo {
     pub uptime: u64,
     pub process: u64,
     pub message: String,
+
+    /// 以下为可选段，未启用时不出现在上报内容中，保持与旧版主端兼容
+    pub cpu_detail: Option<CpuDetail>,
+    pub sensors: Option<Sensors>,
+    pub gpu: Option<Vec<Gpu>>,
+    pub disk_io: Option<Vec<DiskDeviceIo>>,
+    pub network_interfaces: Option<Vec<NetworkInterface>>,
+    pub traffic: Option<Traffic>,
+    pub connection_detail: Option<ConnectionDetail>,
+    pub conntrack: Option<Conntrack>,
+    pub pressure: Option<Pressure>,
+}
+
+// 手动实现 Serialize，跳过未启用的可选段
+impl Serialize for RealTimeInfo {
+    fn begin(&self) -> Fragment<'_> {
+        let mut fields: Vec<(&'static str, &dyn Serialize)> = vec![
+            ("cpu", &self.cpu),
+            ("ram", &self.ram),
+            ("swap", &self.swap),
+            ("disk", &self.disk),
+            ("load", &self.load),
+            ("network", &self.network),
+            ("connections", &self.connections),
+            ("uptime", &self.uptime),
+            ("process", &self.process),
+            ("message", &self.message),
+        ];
+        if let Some(cpu_detail) = &self.cpu_detail {
+            fields.push(("cpu_detail", cpu_detail));
+        }
+        if let Some(sensors) = &self.sensors {
+            fields.push(("sensors", sensors));
+        }
+        if let Some(gpu) = &self.gpu {
+            fields.push(("gpu", gpu));
+        }
+        if let Some(disk_io) = &self.disk_io {
+            fields.push(("disk_io", disk_io));
+        }
+        if let Some(network_interfaces) = &self.network_interfaces {
+            fields.push(("network_interfaces", network_interfaces));
+        }
+        if let Some(traffic) = &self.traffic {
+            fields.push(("traffic", traffic));
+        }
+        if let Some(connection_detail) = &self.connection_detail {
+            fields.push(("connection_detail", connection_detail));
+        }
+        if let Some(conntrack) = &self.conntrack {
+            fields.push(("conntrack", conntrack));
+        }
+        if let Some(pressure) = &self.pressure {
+            fields.push(("pressure", pressure));
+        }
+
+        Fragment::Map(Box::new(FieldStream(fields.into_iter())))
+    }
+}
+
+struct FieldStream<'a>(std::vec::IntoIter<(&'static str, &'a dyn Serialize)>);
+
+impl ser::Map for FieldStream<'_> {
+    fn next(&mut self) -> Option<(Cow<'_, str>, &dyn Serialize)> {
+        self.0
+            .next()
+            .map(|(key, value)| (Cow::Borrowed(key), value))
+    }
 }
 
 impl RealTimeInfo {
     pub fn build(
         sysinfo_sys: &sysinfo::System,
-        network: &Networks,
+        network: &[(&str, &NetworkData)],
         disk: &Disks,
-        fake: f64,
+        connections: Connections,
     ) -> Self {
-        let cpu = realtime_cpu(sysinfo_sys);
-
-        let ram = realtime_mem(sysinfo_sys);
-        let fake_ram_used = (ram.used as f64 * fake) as u64;
-
-        let swap = realtime_swap(sysinfo_sys);
-        let fake_swap_used = (swap.used as f64 * fake) as u64;
-
-        let disk_info = realtime_disk(disk);
-        let fake_disk_used = (disk_info.used as f64 * fake) as u64;
-
-        let load = realtime_load();
-        let fake_load1 = load.load1 * fake;
-        let fake_load5 = load.load5 * fake;
-        let fake_load15 = load.load15 * fake;
+        let realtime_info = Self {
+            cpu: realtime_cpu(sysinfo_sys),
+            ram: realtime_mem(sysinfo_sys),
+            swap: realtime_swap(sysinfo_sys),
+            disk: realtime_disk(disk),
+            load: realtime_load(),
+            network: realtime_network(network),
+            connections,
+            uptime: realtime_uptime(),
+            process: realtime_process(),
+            message: String::new(),
+            cpu_detail: None,
+            sensors: None,
+            gpu: None,
+            disk_io: None,
+            network_interfaces: None,
+            traffic: None,
+            connection_detail: None,
+            conntrack: None,
+            pressure: None,
+        };
 
-        let network_info = realtime_network(network);
-        let fake_network_up = (network_info.up as f64 * fake) as u64;
-        let fake_network_down = (network_info.down as f64 * fake) as u64;
-        let fake_network_total_up = (network_info.total_up as f64 * fake) as u64;
-        let fake_network_total_down = (network_info.total_down as f64 * fake) as u64;
+        debug!("实时信息获取成功: {realtime_info:?}");
 
-        let connections = realtime_connections();
-        let fake_connections_tcp = (connections.tcp as f64 * fake) as u64;
-        let fake_connections_udp = (connections.udp as f64 * fake) as u64;
+        realtime_info
+    }
 
-        let process = realtime_process();
-        let fake_process = (process as f64 * fake) as u64;
+    /// 按虚假倍率缩放后的副本，同一次采集可以按不同倍率上报给多个主端
+    pub fn with_fake(&self, fake: f64) -> Self {
+        let scale = |value: u64| (value as f64 * fake) as u64;
 
-        let realtime_info = Self {
-            cpu,
+        Self {
+            cpu: self.cpu.clone(),
             ram: Ram {
-                used: fake_ram_used,
+                used: scale(self.ram.used),
             },
             swap: Swap {
-                used: fake_swap_used,
+                used: scale(self.swap.used),
             },
             disk: Disk {
-                used: fake_disk_used,
+                used: scale(self.disk.used),
+                io: self.disk.io.as_ref().map(|io| DiskIo {
+                    read_bytes: scale(io.read_bytes),
+                    write_bytes: scale(io.write_bytes),
+                    read_iops: io.read_iops * fake,
+                    write_iops: io.write_iops * fake,
+                }),
             },
             load: Load {
-                load1: fake_load1,
-                load5: fake_load5,
-                load15: fake_load15,
+                load1: self.load.load1 * fake,
+                load5: self.load.load5 * fake,
+                load15: self.load.load15 * fake,
             },
             network: Network {
-                up: fake_network_up,
-                down: fake_network_down,
-                total_up: fake_network_total_up,
-                total_down: fake_network_total_down,
+                up: scale(self.network.up),
+                down: scale(self.network.down),
+                total_up: scale(self.network.total_up),
+                total_down: scale(self.network.total_down),
             },
             connections: Connections {
-                tcp: fake_connections_tcp,
-                udp: fake_connections_udp,
+                tcp: scale(self.connections.tcp),
+                udp: scale(self.connections.udp),
             },
-            uptime: realtime_uptime(),
-            process: fake_process,
-            message: String::new(),
-        };
-
-        debug!("实时信息获取成功: {realtime_info:?}");
-
-        realtime_info
+            uptime: self.uptime,
+            process: scale(self.process),
+            message: self.message.clone(),
+            cpu_detail: self.cpu_detail.clone(),
+            sensors: self.sensors.clone(),
+            gpu: self.gpu.as_ref().map(|gpus| {
+                gpus.iter()
+                    .map(|gpu| Gpu {
+                        mem_used: gpu.mem_used.map(scale),
+                        mem_total: gpu.mem_total.map(scale),
+                        ..gpu.clone()
+                    })
+                    .collect()
+            }),
+            disk_io: self.disk_io.as_ref().map(|devices| {
+                devices
+                    .iter()
+                    .map(|device| DiskDeviceIo {
+                        read_bytes: scale(device.read_bytes),
+                        write_bytes: scale(device.write_bytes),
+                        read_iops: device.read_iops * fake,
+                        write_iops: device.write_iops * fake,
+                        ..device.clone()
+                    })
+                    .collect()
+            }),
+            network_interfaces: self.network_interfaces.as_ref().map(|interfaces| {
+                interfaces
+                    .iter()
+                    .map(|interface| NetworkInterface {
+                        up: scale(interface.up),
+                        down: scale(interface.down),
+                        total_up: scale(interface.total_up),
+                        total_down: scale(interface.total_down),
+                        packets_up: scale(interface.packets_up),
+                        packets_down: scale(interface.packets_down),
+                        ..interface.clone()
+                    })
+                    .collect()
+            }),
+            traffic: self.traffic.as_ref().map(|traffic| Traffic {
+                cycle_up: scale(traffic.cycle_up),
+                cycle_down: scale(traffic.cycle_down),
+                today_up: scale(traffic.today_up),
+                today_down: scale(traffic.today_down),
+                ..traffic.clone()
+            }),
+            connection_detail: self.connection_detail.as_ref().map(|detail| {
+                let states = &detail.tcp_states;
+                ConnectionDetail {
+                    tcp_states: TcpStates {
+                        established: scale(states.established),
+                        syn_sent: scale(states.syn_sent),
+                        syn_recv: scale(states.syn_recv),
+                        fin_wait1: scale(states.fin_wait1),
+                        fin_wait2: scale(states.fin_wait2),
+                        time_wait: scale(states.time_wait),
+                        close: scale(states.close),
+                        close_wait: scale(states.close_wait),
+                        last_ack: scale(states.last_ack),
+                        listen: scale(states.listen),
+                        closing: scale(states.closing),
+                    },
+                    listening: detail.listening.clone(),
+                    top_talkers: detail
+                        .top_talkers
+                        .iter()
+                        .map(|talker| TopTalker {
+                            connections: scale(talker.connections),
+                            up: scale(talker.up),
+                            down: scale(talker.down),
+                            ..talker.clone()
+                        })
+                        .collect(),
+                }
+            }),
+            conntrack: self.conntrack.as_ref().map(|conntrack| Conntrack {
+                count: scale(conntrack.count),
+                max: scale(conntrack.max),
+                drop: scale(conntrack.drop),
+                insert_failed: scale(conntrack.insert_failed),
+                early_drop: scale(conntrack.early_drop),
+                invalid: scale(conntrack.invalid),
+                ..conntrack.clone()
+            }),
+            pressure: self.pressure.clone(),
+        }
     }
 }
@@ -0,0 +1,199 @@
+use crate::command_parser::Args;
+use crate::utils::{ConnectionUrls, connect_ws};
+use log::{info, warn};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 主端列表与故障转移策略
+///
+/// 第一个主端为主用，连续连接失败达到阈值后按顺序切换到下一个；
+/// 使用备用主端期间定期探测主用主端，恢复后回切
+#[derive(Debug)]
+pub struct ServerPool {
+    name: String,
+    servers: Vec<ConnectionUrls>,
+    current: usize,
+    consecutive_failures: u32,
+    failover_threshold: u32,
+    failback_interval: Duration,
+    last_probe: Instant,
+}
+
+impl ServerPool {
+    pub fn new(name: &str, servers: Vec<ConnectionUrls>, args: &Args) -> Self {
+        Self {
+            name: name.to_string(),
+            servers,
+            current: 0,
+            consecutive_failures: 0,
+            failover_threshold: args.failover_threshold.max(1),
+            failback_interval: Duration::from_secs(args.failback_interval),
+            last_probe: Instant::now(),
+        }
+    }
+
+    pub fn current(&self) -> &ConnectionUrls {
+        &self.servers[self.current]
+    }
+
+    pub fn connected(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// 记录一次连接失败，达到阈值时切换到下一个主端
+    pub fn failed(&mut self) {
+        if self.servers.len() < 2 {
+            return;
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures < self.failover_threshold {
+            return;
+        }
+
+        let from = self.current;
+        self.current = (self.current + 1) % self.servers.len();
+        self.consecutive_failures = 0;
+        self.last_probe = Instant::now();
+        warn!(
+            "[{}] 连续 {} 次连接失败，从主端 #{from} 切换到主端 #{}: {}",
+            self.name,
+            self.failover_threshold,
+            self.current,
+            self.servers[self.current].ws_real_time
+        );
+    }
+
+    /// 当前使用备用主端且已到探测时间时，返回主用主端
+    pub fn failback_probe_target(&mut self) -> Option<ConnectionUrls> {
+        if self.current == 0
+            || self.failback_interval.is_zero()
+            || self.last_probe.elapsed() < self.failback_interval
+        {
+            return None;
+        }
+
+        self.last_probe = Instant::now();
+        Some(self.servers[0].clone())
+    }
+
+    pub fn fail_back(&mut self) {
+        info!(
+            "[{}] 主用主端已恢复，从主端 #{} 回切: {}",
+            self.name, self.current, self.servers[0].ws_real_time
+        );
+        self.current = 0;
+        self.consecutive_failures = 0;
+    }
+}
+
+/// 尝试与主端建立 WebSocket 连接，成功后立即关闭
+pub async fn probe_server(server: ConnectionUrls) -> bool {
+    match connect_ws(&server.ws_real_time, &server.auth).await {
+        Ok(mut ws_stream) => {
+            let _ = ws_stream.close(None).await;
+            true
+        }
+        Err(e) => {
+            info!("主用主端仍不可用: {e}");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::build_server_list;
+    use clap::Parser;
+
+    const SERVERS: [&str; 3] = ["http://a", "http://b", "http://c"];
+
+    fn pool(servers: &[&str], extra: &[&str]) -> ServerPool {
+        let mut argv = vec!["komari-monitor-rs", "--http-server", "http://a", "-t", "t"];
+        argv.extend_from_slice(extra);
+        let args = Args::try_parse_from(argv).unwrap();
+        let http_servers: Vec<String> = servers.iter().map(ToString::to_string).collect();
+        let servers = build_server_list(&http_servers, &[], &args.token, &args.auth_mode).unwrap();
+        ServerPool::new("test", servers, &args)
+    }
+
+    fn current_host(pool: &ServerPool) -> &str {
+        pool.current()
+            .ws_real_time
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap()
+    }
+
+    #[test]
+    fn rotates_after_threshold_and_wraps_around() {
+        let mut pool = pool(&SERVERS, &["--failover-threshold", "2"]);
+        assert_eq!(current_host(&pool), "a");
+
+        pool.failed();
+        assert_eq!(current_host(&pool), "a");
+        pool.failed();
+        assert_eq!(current_host(&pool), "b");
+
+        // 连接成功后重新计数
+        pool.failed();
+        pool.connected();
+        pool.failed();
+        assert_eq!(current_host(&pool), "b");
+        pool.failed();
+        assert_eq!(current_host(&pool), "c");
+
+        // 所有主端都失败时循环重试
+        pool.failed();
+        pool.failed();
+        assert_eq!(current_host(&pool), "a");
+    }
+
+    #[test]
+    fn single_server_never_rotates() {
+        let mut pool = pool(&SERVERS[..1], &["--failover-threshold", "1"]);
+        for _ in 0..5 {
+            pool.failed();
+        }
+        assert_eq!(current_host(&pool), "a");
+        assert!(pool.failback_probe_target().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn probes_primary_periodically_and_fails_back() {
+        let mut pool = pool(
+            &SERVERS,
+            &["--failover-threshold", "1", "--failback-interval", "60"],
+        );
+        assert!(
+            pool.failback_probe_target().is_none(),
+            "使用主用主端时不探测"
+        );
+
+        pool.failed();
+        assert_eq!(current_host(&pool), "b");
+        assert!(pool.failback_probe_target().is_none());
+
+        tokio::time::advance(Duration::from_mins(1)).await;
+        let probe = pool.failback_probe_target().unwrap();
+        assert_eq!(probe.ws_real_time, pool.servers[0].ws_real_time);
+        assert!(pool.failback_probe_target().is_none(), "探测后重新计时");
+
+        pool.fail_back();
+        assert_eq!(current_host(&pool), "a");
+        assert!(pool.failback_probe_target().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_failback_interval_disables_probing() {
+        let mut pool = pool(
+            &SERVERS,
+            &["--failover-threshold", "1", "--failback-interval", "0"],
+        );
+        pool.failed();
+        tokio::time::advance(Duration::from_hours(1)).await;
+        assert!(pool.failback_probe_target().is_none());
+    }
+}
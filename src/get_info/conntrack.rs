@@ -0,0 +1,218 @@
+use crate::data_struct::Conntrack;
+use log::{info, trace, warn};
+use std::fs;
+
+const CONNTRACK_COUNT: &str = "/proc/sys/net/netfilter/nf_conntrack_count";
+const CONNTRACK_MAX: &str = "/proc/sys/net/netfilter/nf_conntrack_max";
+const CONNTRACK_STAT: &str = "/proc/net/stat/nf_conntrack";
+
+/// `/proc/net/stat/nf_conntrack` 中各 CPU 计数的合计
+#[derive(Debug, Clone, Copy, Default)]
+struct ConntrackStat {
+    drop: u64,
+    insert_failed: u64,
+    early_drop: u64,
+    invalid: u64,
+}
+
+/// 读取连接跟踪表的使用量与丢弃计数，未加载 `nf_conntrack` 模块时不上报
+///
+/// 使用率达到阈值，或两次采集之间出现新的 `drop`、`insert_failed`、`early_drop` 时标记告警并输出日志
+#[derive(Debug)]
+pub struct ConntrackCollector {
+    alert_percent: f64,
+    previous: Option<ConntrackStat>,
+    alerting: bool,
+}
+
+impl ConntrackCollector {
+    pub fn new(alert_percent: u8) -> Self {
+        if fs::metadata(CONNTRACK_COUNT).is_err() {
+            warn!("未找到 {CONNTRACK_COUNT}，nf_conntrack 模块加载后才会上报连接跟踪信息");
+        }
+        Self {
+            alert_percent: f64::from(alert_percent),
+            previous: None,
+            alerting: false,
+        }
+    }
+
+    pub fn set_alert_percent(&mut self, alert_percent: u8) {
+        self.alert_percent = f64::from(alert_percent);
+    }
+
+    pub fn collect(&mut self) -> Option<Conntrack> {
+        let count = read_value(CONNTRACK_COUNT)?;
+        let max = read_value(CONNTRACK_MAX)?;
+        let stat = fs::read_to_string(CONNTRACK_STAT)
+            .ok()
+            .and_then(|content| parse_stat(&content))
+            .unwrap_or_default();
+
+        let conntrack = self.update(count, max, stat);
+        trace!("REALTIME CONNTRACK 获取成功: {conntrack:?}");
+        Some(conntrack)
+    }
+
+    /// 与上次采集的丢弃计数比较并更新告警状态
+    fn update(&mut self, count: u64, max: u64, stat: ConntrackStat) -> Conntrack {
+        let usage = if max > 0 {
+            count as f64 / max as f64 * 100.0
+        } else {
+            0.0
+        };
+        let dropped = self.previous.map_or(0, |previous| {
+            (stat.drop.saturating_sub(previous.drop))
+                + (stat.insert_failed.saturating_sub(previous.insert_failed))
+                + (stat.early_drop.saturating_sub(previous.early_drop))
+        });
+        self.previous = Some(stat);
+
+        let over_threshold = self.alert_percent > 0.0 && usage >= self.alert_percent;
+        let alert = over_threshold || dropped > 0;
+        if alert && !self.alerting {
+            warn!(
+                "连接跟踪表告警: 已使用 {count}/{max} ({usage:.1}%)，本周期新增丢弃 {dropped} 个连接"
+            );
+        } else if !alert && self.alerting {
+            info!("连接跟踪表恢复正常: 已使用 {count}/{max} ({usage:.1}%)");
+        }
+        self.alerting = alert;
+
+        Conntrack {
+            count,
+            max,
+            usage,
+            drop: stat.drop,
+            insert_failed: stat.insert_failed,
+            early_drop: stat.early_drop,
+            invalid: stat.invalid,
+            alert,
+        }
+    }
+}
+
+fn read_value(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// 首行为列名，其后每个 CPU 一行，数值为十六进制
+fn parse_stat(content: &str) -> Option<ConntrackStat> {
+    let mut lines = content.lines();
+    let columns: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let index = |name: &str| columns.iter().position(|column| *column == name);
+    let (drop, insert_failed, early_drop, invalid) = (
+        index("drop"),
+        index("insert_failed"),
+        index("early_drop"),
+        index("invalid"),
+    );
+
+    let mut stat = ConntrackStat::default();
+    for line in lines {
+        let values: Vec<u64> = line
+            .split_whitespace()
+            .map(|value| u64::from_str_radix(value, 16).unwrap_or(0))
+            .collect();
+        let value = |index: Option<usize>| index.and_then(|i| values.get(i)).copied().unwrap_or(0);
+        stat.drop += value(drop);
+        stat.insert_failed += value(insert_failed);
+        stat.early_drop += value(early_drop);
+        stat.invalid += value(invalid);
+    }
+    Some(stat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Linux 6.x 的列名，每个 CPU 一行
+    const STAT: &str = "\
+entries  clashres found new invalid ignore delete chainlength insert insert_failed drop early_drop icmp_error  expect_new expect_create expect_delete search_restart clash_resolve chaintoolong
+0000002a  00000000 00000000 00000000 00000003 00000000 00000000 00000000 00000000 00000001 00000010 00000000 00000000  00000000 00000000 00000000 00000000 00000000 00000000
+0000002a  00000000 00000000 00000000 0000000a 00000000 00000000 00000000 00000000 00000000 000000ff 00000002 00000000  00000000 00000000 00000000 00000000 00000000 00000000
+";
+
+    fn stat(drop: u64, insert_failed: u64, early_drop: u64) -> ConntrackStat {
+        ConntrackStat {
+            drop,
+            insert_failed,
+            early_drop,
+            invalid: 0,
+        }
+    }
+
+    #[test]
+    fn parse_stat_sums_hex_counters_across_cpus() {
+        let stat = parse_stat(STAT).unwrap();
+        assert_eq!(stat.drop, 0x10 + 0xff);
+        assert_eq!(stat.insert_failed, 1);
+        assert_eq!(stat.early_drop, 2);
+        assert_eq!(stat.invalid, 3 + 10);
+    }
+
+    #[test]
+    fn parse_stat_locates_columns_by_name() {
+        // 旧内核的列顺序不同，且没有 early_drop 之后的列
+        let stat = parse_stat(
+            "entries searched found new invalid ignore delete delete_list insert insert_failed drop early_drop\n\
+             00000001 00000000 00000000 00000000 00000004 00000000 00000000 00000000 00000000 00000005 00000006 00000007\n",
+        )
+        .unwrap();
+        assert_eq!(
+            (stat.drop, stat.insert_failed, stat.early_drop, stat.invalid),
+            (6, 5, 7, 4)
+        );
+    }
+
+    #[test]
+    fn parse_stat_tolerates_missing_columns_and_bad_values() {
+        let stat = parse_stat("entries drop\n00000001 zz\n00000001 00000002\n00000001\n").unwrap();
+        assert_eq!(stat.drop, 2);
+        assert_eq!(
+            (stat.insert_failed, stat.early_drop, stat.invalid),
+            (0, 0, 0)
+        );
+
+        assert!(parse_stat("").is_none());
+        let stat = parse_stat("entries drop\n").unwrap();
+        assert_eq!(stat.drop, 0);
+    }
+
+    #[test]
+    fn alert_on_usage_threshold() {
+        let mut collector = ConntrackCollector {
+            alert_percent: 80.0,
+            previous: None,
+            alerting: false,
+        };
+        let conntrack = collector.update(800, 1000, stat(0, 0, 0));
+        assert!(conntrack.alert);
+        assert!((conntrack.usage - 80.0).abs() < f64::EPSILON);
+
+        assert!(!collector.update(799, 1000, stat(0, 0, 0)).alert);
+        assert!(!collector.alerting);
+
+        collector.set_alert_percent(0);
+        assert!(!collector.update(1000, 1000, stat(0, 0, 0)).alert);
+        assert!(collector.update(0, 0, stat(0, 0, 0)).usage.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn alert_on_new_drops_between_collections() {
+        let mut collector = ConntrackCollector {
+            alert_percent: 0.0,
+            previous: None,
+            alerting: false,
+        };
+        // 首次采集没有基准，已有的丢弃计数不触发告警
+        assert!(!collector.update(1, 1000, stat(50, 5, 5)).alert);
+        assert!(collector.update(1, 1000, stat(51, 5, 5)).alert);
+        assert!(collector.update(1, 1000, stat(51, 6, 5)).alert);
+        assert!(collector.update(1, 1000, stat(51, 6, 7)).alert);
+        assert!(!collector.update(1, 1000, stat(51, 6, 7)).alert);
+        // 计数被重置时不算新增丢弃
+        assert!(!collector.update(1, 1000, stat(0, 0, 0)).alert);
+    }
+}
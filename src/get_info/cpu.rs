@@ -1,6 +1,7 @@
-use crate::data_struct::Cpu;
+use crate::data_struct::{Cpu, CpuCore, CpuDetail, CpuTimes};
 use log::trace;
 use std::collections::HashSet;
+use std::fs;
 use sysinfo::System;
 
 pub fn arch() -> String {
@@ -47,3 +48,191 @@ pub fn realtime_cpu(sysinfo_sys: &System) -> Cpu {
     trace!("REALTIME CPU 获取成功: {cpu:?}");
     cpu
 }
+
+/// 采集每个核心的占用率与频率，以及两次采集之间 /proc/stat 中的 CPU 时间分布
+#[derive(Debug)]
+pub struct CpuDetailCollector {
+    /// 每个核心硬件支持的频率范围 (MHz)，启动时读取一次
+    frequency_limits: Vec<(Option<u64>, Option<u64>)>,
+    previous_times: Option<[u64; 8]>,
+}
+
+impl CpuDetailCollector {
+    pub fn new(sysinfo_sys: &System) -> Self {
+        let frequency_limits = sysinfo_sys
+            .cpus()
+            .iter()
+            .map(|cpu| {
+                (
+                    read_cpufreq_mhz(cpu.name(), "cpuinfo_min_freq"),
+                    read_cpufreq_mhz(cpu.name(), "cpuinfo_max_freq"),
+                )
+            })
+            .collect();
+
+        Self {
+            frequency_limits,
+            previous_times: read_proc_stat_times(),
+        }
+    }
+
+    pub fn collect(&mut self, sysinfo_sys: &System) -> CpuDetail {
+        let cores = sysinfo_sys
+            .cpus()
+            .iter()
+            .enumerate()
+            .map(|(index, cpu)| {
+                let (min_frequency, max_frequency) = self
+                    .frequency_limits
+                    .get(index)
+                    .copied()
+                    .unwrap_or_default();
+                CpuCore {
+                    usage: f64::from(cpu.cpu_usage()),
+                    frequency: cpu.frequency(),
+                    min_frequency,
+                    max_frequency,
+                }
+            })
+            .collect();
+
+        let current_times = read_proc_stat_times();
+        let times = match (self.previous_times, current_times) {
+            (Some(previous), Some(current)) => cpu_times_between(&previous, &current),
+            _ => None,
+        };
+        self.previous_times = current_times;
+
+        let cpu_detail = CpuDetail { cores, times };
+        trace!("REALTIME CPU DETAIL 获取成功: {cpu_detail:?}");
+        cpu_detail
+    }
+}
+
+/// 读取 cpufreq 中以 kHz 为单位的频率并转换为 MHz
+fn read_cpufreq_mhz(cpu_name: &str, file: &str) -> Option<u64> {
+    let khz = fs::read_to_string(cpufreq_path(cpu_name, file)?)
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(khz / 1000)
+}
+
+/// sysinfo 的 CPU 名称 (`cpuN`) 与 sysfs 编号一致，CPU 离线时编号不连续，不能使用枚举序号
+fn cpufreq_path(cpu_name: &str, file: &str) -> Option<String> {
+    let number: usize = cpu_name.strip_prefix("cpu")?.parse().ok()?;
+    Some(format!(
+        "/sys/devices/system/cpu/cpu{number}/cpufreq/{file}"
+    ))
+}
+
+fn read_proc_stat_times() -> Option<[u64; 8]> {
+    parse_proc_stat(&fs::read_to_string("/proc/stat").ok()?)
+}
+
+/// 解析 /proc/stat 中汇总的 user、nice、system、idle、iowait、irq、softirq、steal 时间
+///
+/// guest 时间已计入 user 与 nice，不再单独统计
+fn parse_proc_stat(stat: &str) -> Option<[u64; 8]> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+
+    let mut times = [0u64; 8];
+    let mut fields = line.split_whitespace().skip(1);
+    for time in &mut times {
+        // 较旧的内核没有 steal 等字段，缺失时视为 0
+        *time = fields.next().map_or(Ok(0), str::parse).ok()?;
+    }
+    Some(times)
+}
+
+/// 计数变小 (如部分内核的 iowait) 时按 0 计算
+fn cpu_times_between(previous: &[u64; 8], current: &[u64; 8]) -> Option<CpuTimes> {
+    let mut delta = [0u64; 8];
+    for (index, value) in delta.iter_mut().enumerate() {
+        *value = current[index].saturating_sub(previous[index]);
+    }
+
+    let total: u64 = delta.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let percent = |value: u64| value as f64 / total as f64 * 100.0;
+
+    Some(CpuTimes {
+        user: percent(delta[0]),
+        nice: percent(delta[1]),
+        system: percent(delta[2]),
+        idle: percent(delta[3]),
+        iowait: percent(delta[4]),
+        irq: percent(delta[5]),
+        softirq: percent(delta[6]),
+        steal: percent(delta[7]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpufreq_path_uses_cpu_number_from_name() {
+        assert_eq!(
+            cpufreq_path("cpu12", "cpuinfo_max_freq").as_deref(),
+            Some("/sys/devices/system/cpu/cpu12/cpufreq/cpuinfo_max_freq")
+        );
+        assert_eq!(cpufreq_path("0", "cpuinfo_max_freq"), None);
+        assert_eq!(cpufreq_path("cpu", "cpuinfo_max_freq"), None);
+    }
+
+    #[test]
+    fn parses_aggregate_line_and_ignores_guest_fields() {
+        let stat = "cpu  100 2 30 400 5 6 7 8 90 10\n\
+                    cpu0 50 1 15 200 2 3 3 4 45 5\n\
+                    intr 12345\n";
+        assert_eq!(parse_proc_stat(stat), Some([100, 2, 30, 400, 5, 6, 7, 8]));
+    }
+
+    #[test]
+    fn missing_fields_on_old_kernels_are_zero() {
+        assert_eq!(
+            parse_proc_stat("cpu  1 2 3 4\n"),
+            Some([1, 2, 3, 4, 0, 0, 0, 0])
+        );
+        assert_eq!(parse_proc_stat("cpu  1 x 3 4\n"), None);
+        assert_eq!(parse_proc_stat("cpu0 1 2 3 4\n"), None);
+    }
+
+    fn assert_percent(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn times_between_samples_are_percentages() {
+        let times = cpu_times_between(
+            &[100, 0, 50, 800, 10, 0, 0, 0],
+            &[130, 0, 60, 840, 20, 0, 0, 10],
+        )
+        .unwrap();
+        assert_percent(times.user, 30.0);
+        assert_percent(times.system, 10.0);
+        assert_percent(times.idle, 40.0);
+        assert_percent(times.iowait, 10.0);
+        assert_percent(times.steal, 10.0);
+    }
+
+    #[test]
+    fn decreasing_counter_counts_as_zero() {
+        let times = cpu_times_between(
+            &[100, 0, 0, 100, 50, 0, 0, 0],
+            &[150, 0, 0, 150, 40, 0, 0, 0],
+        )
+        .unwrap();
+        assert_percent(times.iowait, 0.0);
+        assert_percent(times.user, 50.0);
+        assert_percent(times.idle, 50.0);
+
+        let unchanged = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert!(cpu_times_between(&unchanged, &unchanged).is_none());
+    }
+}
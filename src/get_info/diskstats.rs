@@ -0,0 +1,338 @@
+use crate::data_struct::{DiskDeviceIo, DiskIo};
+use crate::get_info::filter::NameFilter;
+use log::trace;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// /proc/diskstats 中的扇区固定为 512 字节，与设备的实际扇区大小无关
+const SECTOR_SIZE: u64 = 512;
+
+/// 未指定设备时默认排除的虚拟块设备
+///
+/// device-mapper (LVM、LUKS) 与软 RAID 设备的 I/O 已计入其下层磁盘，统计会重复计算
+const DEFAULT_EXCLUDED_DEVICES: [&str; 5] = ["loop*", "ram*", "zram*", "dm-*", "md*"];
+
+/// /proc/diskstats 中单个设备的累计计数
+#[derive(Debug, Clone, Copy, Default)]
+struct DiskCounters {
+    reads: u64,
+    read_sectors: u64,
+    read_ms: u64,
+    writes: u64,
+    write_sectors: u64,
+    write_ms: u64,
+    io_ms: u64,
+}
+
+/// 选择参与统计的块设备
+///
+/// 未指定设备时只选择物理磁盘 (不含分区以及 loop、ram、zram、dm 与 md 设备)，
+/// 指定后按名称匹配 (支持通配符)
+#[derive(Debug, Clone)]
+pub struct DeviceSelector {
+    filter: NameFilter,
+    /// 只选择整块磁盘时为 /sys/block 目录
+    sys_block: Option<PathBuf>,
+    /// 设备是否为整块磁盘，避免每个周期都访问 sysfs
+    whole_disks: HashMap<String, bool>,
+}
+
+impl DeviceSelector {
+    pub fn new(devices: &[String]) -> Self {
+        Self::with_sys_block(devices, Path::new("/sys/block"))
+    }
+
+    fn with_sys_block(devices: &[String], sys_block: &Path) -> Self {
+        if devices.is_empty() {
+            Self {
+                filter: NameFilter::new(&[], &DEFAULT_EXCLUDED_DEVICES.map(str::to_string)),
+                sys_block: Some(sys_block.to_path_buf()),
+                whole_disks: HashMap::new(),
+            }
+        } else {
+            Self {
+                filter: NameFilter::new(devices, &[]),
+                sys_block: None,
+                whole_disks: HashMap::new(),
+            }
+        }
+    }
+
+    fn includes(&mut self, name: &str) -> bool {
+        if !self.filter.allows(name) {
+            return false;
+        }
+        let Some(sys_block) = &self.sys_block else {
+            return true;
+        };
+        // /sys/block 下只有整块磁盘，分区位于各磁盘的子目录中
+        if let Some(&whole_disk) = self.whole_disks.get(name) {
+            return whole_disk;
+        }
+        let whole_disk = sys_block.join(name).exists();
+        self.whole_disks.insert(name.to_string(), whole_disk);
+        whole_disk
+    }
+}
+
+/// 根据两次采集之间 /proc/diskstats 的差值计算每个块设备的吞吐量、IOPS、平均等待时间与利用率
+#[derive(Debug)]
+pub struct DiskStatsCollector {
+    selector: DeviceSelector,
+    previous: HashMap<String, DiskCounters>,
+    previous_at: Instant,
+}
+
+impl DiskStatsCollector {
+    pub fn new(devices: &[String]) -> Self {
+        Self {
+            selector: DeviceSelector::new(devices),
+            previous: read_diskstats(),
+            previous_at: Instant::now(),
+        }
+    }
+
+    /// 返回每个设备的统计与所有设备的合计
+    pub fn collect(&mut self) -> (Vec<DiskDeviceIo>, DiskIo) {
+        let current = read_diskstats();
+        let elapsed_ms = self.previous_at.elapsed().as_secs_f64() * 1000.0;
+        self.previous_at = Instant::now();
+
+        let mut names: Vec<&String> = current
+            .keys()
+            .filter(|name| self.selector.includes(name))
+            .collect();
+        names.sort();
+
+        let mut devices = Vec::with_capacity(names.len());
+        let mut total = DiskIo {
+            read_bytes: 0,
+            write_bytes: 0,
+            read_iops: 0.0,
+            write_iops: 0.0,
+        };
+        for name in names {
+            let now = current[name];
+            // 新出现的设备本周期没有可比较的基准
+            let before = self.previous.get(name).copied().unwrap_or(now);
+            let device = device_io(name, &before, &now, elapsed_ms);
+
+            total.read_bytes += device.read_bytes;
+            total.write_bytes += device.write_bytes;
+            total.read_iops += device.read_iops;
+            total.write_iops += device.write_iops;
+            devices.push(device);
+        }
+        self.previous = current;
+
+        trace!("REALTIME DISK IO 获取成功: {devices:?}, {total:?}");
+        (devices, total)
+    }
+}
+
+/// 选中的块设备自开机以来累计读取与写入的字节数，按名称排序
+pub fn disk_totals(selector: &mut DeviceSelector) -> Vec<(String, u64, u64)> {
+    let mut totals: Vec<(String, u64, u64)> = read_diskstats()
+        .into_iter()
+        .filter(|(name, _)| selector.includes(name))
+        .map(|(name, counters)| {
+            (
+                name,
+                counters.read_sectors.saturating_mul(SECTOR_SIZE),
+                counters.write_sectors.saturating_mul(SECTOR_SIZE),
+            )
+        })
+        .collect();
+    totals.sort_by(|a, b| a.0.cmp(&b.0));
+    totals
+}
+
+fn device_io(
+    name: &str,
+    before: &DiskCounters,
+    now: &DiskCounters,
+    elapsed_ms: f64,
+) -> DiskDeviceIo {
+    let delta = |now: u64, before: u64| now.saturating_sub(before);
+    let reads = delta(now.reads, before.reads);
+    let writes = delta(now.writes, before.writes);
+    let per_second = |value: u64| {
+        if elapsed_ms > 0.0 {
+            value as f64 * 1000.0 / elapsed_ms
+        } else {
+            0.0
+        }
+    };
+
+    let ios = reads + writes;
+    let wait_ms = delta(now.read_ms, before.read_ms) + delta(now.write_ms, before.write_ms);
+
+    DiskDeviceIo {
+        name: name.to_string(),
+        read_bytes: per_second(delta(now.read_sectors, before.read_sectors) * SECTOR_SIZE) as u64,
+        write_bytes: per_second(delta(now.write_sectors, before.write_sectors) * SECTOR_SIZE)
+            as u64,
+        read_iops: per_second(reads),
+        write_iops: per_second(writes),
+        await_ms: if ios > 0 {
+            wait_ms as f64 / ios as f64
+        } else {
+            0.0
+        },
+        utilization: if elapsed_ms > 0.0 {
+            (delta(now.io_ms, before.io_ms) as f64 / elapsed_ms * 100.0).min(100.0)
+        } else {
+            0.0
+        },
+    }
+}
+
+fn read_diskstats() -> HashMap<String, DiskCounters> {
+    fs::read_to_string("/proc/diskstats")
+        .map(|content| parse_diskstats(&content))
+        .unwrap_or_default()
+}
+
+fn parse_diskstats(content: &str) -> HashMap<String, DiskCounters> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 14 {
+                return None;
+            }
+            let field = |index: usize| fields[index].parse::<u64>().ok();
+            let counters = DiskCounters {
+                reads: field(3)?,
+                read_sectors: field(5)?,
+                read_ms: field(6)?,
+                writes: field(7)?,
+                write_sectors: field(9)?,
+                write_ms: field(10)?,
+                io_ms: field(12)?,
+            };
+            Some((fields[2].to_string(), counters))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISKSTATS: &str = "\
+   7       0 loop0 43 0 2152 9 0 0 0 0 0 20 9 0 0 0 0 0 0
+ 259       0 nvme0n1 1000 20 80000 500 2000 30 160000 1500 3 1200 2000 0 0 0 0 100 10
+ 259       1 nvme0n1p1 900 20 70000 450 1900 30 150000 1400 0 1100 1850
+   8       0 sda 5 0 40
+";
+
+    #[test]
+    fn parses_diskstats_and_skips_short_lines() {
+        let stats = parse_diskstats(DISKSTATS);
+        assert_eq!(stats.len(), 3);
+        let nvme = stats["nvme0n1"];
+        assert_eq!(
+            (nvme.reads, nvme.read_sectors, nvme.read_ms),
+            (1000, 80000, 500)
+        );
+        assert_eq!(
+            (nvme.writes, nvme.write_sectors, nvme.write_ms, nvme.io_ms),
+            (2000, 160_000, 1500, 1200)
+        );
+        assert!(!stats.contains_key("sda"));
+    }
+
+    #[test]
+    fn device_io_computes_rates_from_deltas() {
+        let before = DiskCounters {
+            reads: 100,
+            read_sectors: 1000,
+            read_ms: 50,
+            writes: 200,
+            write_sectors: 4000,
+            write_ms: 150,
+            io_ms: 1000,
+        };
+        let now = DiskCounters {
+            reads: 150,
+            read_sectors: 3048,
+            read_ms: 100,
+            writes: 250,
+            write_sectors: 8096,
+            write_ms: 250,
+            io_ms: 1250,
+        };
+
+        let io = device_io("sda", &before, &now, 500.0);
+        assert_eq!(io.read_bytes, 2048 * 512 * 2);
+        assert_eq!(io.write_bytes, 4096 * 512 * 2);
+        assert!((io.read_iops - 100.0).abs() < 1e-9);
+        assert!((io.write_iops - 100.0).abs() < 1e-9);
+        // (50 + 100) ms / 100 次 I/O
+        assert!((io.await_ms - 1.5).abs() < 1e-9);
+        assert!((io.utilization - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn device_io_handles_counter_reset_and_idle_devices() {
+        let before = DiskCounters {
+            reads: 500,
+            read_sectors: 5000,
+            io_ms: 900,
+            ..DiskCounters::default()
+        };
+        let io = device_io("sda", &before, &DiskCounters::default(), 1000.0);
+        assert_eq!(io.read_bytes, 0);
+        assert!(io.read_iops.abs() < f64::EPSILON);
+        assert!(io.await_ms.abs() < f64::EPSILON);
+        assert!(io.utilization.abs() < f64::EPSILON);
+
+        let busy = DiskCounters {
+            io_ms: 5000,
+            ..DiskCounters::default()
+        };
+        let io = device_io("sda", &DiskCounters::default(), &busy, 1000.0);
+        assert!((io.utilization - 100.0).abs() < f64::EPSILON);
+
+        let io = device_io("sda", &DiskCounters::default(), &busy, 0.0);
+        assert!(io.utilization.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn explicit_devices_match_partitions_by_pattern() {
+        let mut selector = DeviceSelector::new(&["nvme0n1p*".to_string()]);
+        assert!(selector.includes("nvme0n1p1"));
+        assert!(!selector.includes("nvme0n1"));
+    }
+
+    #[test]
+    fn default_selects_physical_disks_only() {
+        let sys_block = tempfile::tempdir().unwrap();
+        for device in ["sda", "nvme0n1", "dm-0", "md127", "loop0", "zram0"] {
+            fs::create_dir(sys_block.path().join(device)).unwrap();
+        }
+        let mut selector = DeviceSelector::with_sys_block(&[], sys_block.path());
+
+        let selected: Vec<_> = [
+            "sda",
+            "sda1",
+            "nvme0n1",
+            "nvme0n1p2",
+            "dm-0",
+            "md127",
+            "loop0",
+            "zram0",
+        ]
+        .into_iter()
+        .filter(|name| selector.includes(name))
+        .collect();
+        assert_eq!(selected, ["sda", "nvme0n1"]);
+
+        // 结果已缓存，不再访问 sysfs
+        fs::remove_dir(sys_block.path().join("sda")).unwrap();
+        assert!(selector.includes("sda"));
+    }
+}
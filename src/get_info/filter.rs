@@ -0,0 +1,94 @@
+/// 按名称筛选采集对象，支持 `*` 与 `?` 通配符
+///
+/// 包含列表为空时默认包含所有名称，排除列表优先于包含列表
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl NameFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        Self {
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+        }
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name))
+        {
+            return false;
+        }
+        self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| wildcard_match(pattern, name))
+    }
+}
+
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // 最近一个 `*` 的位置及其当前匹配到的文本位置，用于回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_matches_whole_name() {
+        assert!(wildcard_match("eth0", "eth0"));
+        assert!(!wildcard_match("eth", "eth0"));
+        assert!(wildcard_match("eth?", "eth0"));
+        assert!(!wildcard_match("eth?", "eth10"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("veth*", "veth1234"));
+        assert!(wildcard_match("*temp*", "k10temp/Tctl"));
+        assert!(wildcard_match("a*b*c", "axxbyybc"));
+        assert!(!wildcard_match("a*b*c", "axxbyyb"));
+        assert!(wildcard_match(
+            "coretemp/Package id *",
+            "coretemp/Package id 0"
+        ));
+        assert!(wildcard_match("温度*", "温度传感器"));
+    }
+
+    #[test]
+    fn filter_exclude_takes_precedence() {
+        let filter = NameFilter::new(&["sd*".to_string()], &["sdb".to_string()]);
+        assert!(filter.allows("sda"));
+        assert!(!filter.allows("sdb"));
+        assert!(!filter.allows("nvme0n1"));
+
+        let filter = NameFilter::new(&[], &["loop*".to_string()]);
+        assert!(filter.allows("nvme0n1"));
+        assert!(!filter.allows("loop0"));
+    }
+}
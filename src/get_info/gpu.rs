@@ -0,0 +1,276 @@
+use crate::data_struct::Gpu;
+use log::trace;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 常见显卡厂商的 PCI Vendor ID
+const PCI_VENDORS: [(u16, &str); 12] = [
+    (0x1002, "AMD"),
+    (0x1013, "Cirrus Logic"),
+    (0x102b, "Matrox"),
+    (0x10de, "NVIDIA"),
+    (0x1234, "QEMU"),
+    (0x1414, "Microsoft"),
+    (0x15ad, "VMware"),
+    (0x1a03, "ASPEED"),
+    (0x1af4, "Virtio"),
+    (0x1b36, "Red Hat"),
+    (0x5143, "Qualcomm"),
+    (0x8086, "Intel"),
+];
+
+/// 内置的精简 PCI ID 表，只收录服务器、虚拟机与常见独立显卡，未收录的设备显示厂商名与 ID
+const PCI_DEVICES: [(u16, u16, &str); 40] = [
+    // 虚拟化与服务器板载显卡
+    (0x1013, 0x00b8, "GD 5446"),
+    (0x102b, 0x0522, "MGA G200e"),
+    (0x102b, 0x0534, "G200eR2"),
+    (0x102b, 0x0536, "Integrated Matrox G200eW3"),
+    (0x1234, 0x1111, "Standard VGA"),
+    (0x1414, 0x5353, "Hyper-V Video"),
+    (0x15ad, 0x0405, "SVGA II Adapter"),
+    (0x1a03, 0x2000, "ASPEED Graphics Family"),
+    (0x1af4, 0x1050, "GPU"),
+    (0x1b36, 0x0100, "QXL paravirtual graphic card"),
+    // NVIDIA 数据中心
+    (0x10de, 0x1db4, "Tesla V100 PCIe 16GB"),
+    (0x10de, 0x1eb8, "Tesla T4"),
+    (0x10de, 0x20b0, "A100 SXM4 40GB"),
+    (0x10de, 0x20b5, "A100 PCIe 80GB"),
+    (0x10de, 0x20f1, "A100 PCIe 40GB"),
+    (0x10de, 0x2236, "A10"),
+    (0x10de, 0x2330, "H100 SXM5 80GB"),
+    (0x10de, 0x2331, "H100 PCIe"),
+    (0x10de, 0x26b9, "L40S"),
+    (0x10de, 0x27b8, "L4"),
+    // NVIDIA 消费级
+    (0x10de, 0x1b80, "GeForce GTX 1080"),
+    (0x10de, 0x1e07, "GeForce RTX 2080 Ti"),
+    (0x10de, 0x2204, "GeForce RTX 3090"),
+    (0x10de, 0x2206, "GeForce RTX 3080"),
+    (0x10de, 0x2684, "GeForce RTX 4090"),
+    (0x10de, 0x2704, "GeForce RTX 4080"),
+    // AMD
+    (0x1002, 0x66a1, "Radeon Pro VII / MI50"),
+    (0x1002, 0x738c, "Instinct MI100"),
+    (0x1002, 0x740f, "Instinct MI210"),
+    (0x1002, 0x73bf, "Radeon RX 6800 / 6800 XT / 6900 XT"),
+    (0x1002, 0x73df, "Radeon RX 6700 XT"),
+    (0x1002, 0x744c, "Radeon RX 7900 XT / 7900 XTX"),
+    (0x1002, 0x1638, "Radeon Vega (Cezanne)"),
+    (0x1002, 0x164e, "Radeon Graphics (Raphael)"),
+    // Intel
+    (0x8086, 0x3e92, "UHD Graphics 630"),
+    (0x8086, 0x3e9b, "UHD Graphics 630 (Mobile)"),
+    (0x8086, 0x4680, "UHD Graphics 770"),
+    (0x8086, 0x46a6, "Iris Xe Graphics (Alder Lake)"),
+    (0x8086, 0x9a49, "Iris Xe Graphics (Tiger Lake)"),
+    (0x8086, 0x56a0, "Arc A770"),
+];
+
+/// `/sys/class/drm` 中的一块显卡
+#[derive(Debug, Clone)]
+struct GpuDevice {
+    name: String,
+    device_dir: PathBuf,
+}
+
+/// 所有显卡的名称，多块相同型号的显卡只显示一次
+pub fn gpu_name() -> String {
+    let mut names: Vec<String> = Vec::new();
+    for gpu in detect_gpus(Path::new("/sys/class/drm")) {
+        if !names.contains(&gpu.name) {
+            names.push(gpu.name);
+        }
+    }
+    let name = names.join(", ");
+    trace!("GPU NAME 获取成功: {name}");
+    name
+}
+
+/// 启动时枚举显卡，每个周期读取 sysfs 中暴露的占用率与显存 (目前仅 amdgpu 提供)
+#[derive(Debug)]
+pub struct GpuCollector {
+    gpus: Vec<GpuDevice>,
+}
+
+impl GpuCollector {
+    pub fn new() -> Self {
+        Self::with_root(Path::new("/sys/class/drm"))
+    }
+
+    /// `root` 对应 `/sys/class/drm`，便于在其他目录中构造模拟的 sysfs
+    pub fn with_root(root: &Path) -> Self {
+        Self {
+            gpus: detect_gpus(root),
+        }
+    }
+
+    pub fn collect(&self) -> Vec<Gpu> {
+        let gpus = self
+            .gpus
+            .iter()
+            .map(|gpu| Gpu {
+                name: gpu.name.clone(),
+                usage: read_u64(&gpu.device_dir.join("gpu_busy_percent")).map(|v| v as f64),
+                mem_used: read_u64(&gpu.device_dir.join("mem_info_vram_used")),
+                mem_total: read_u64(&gpu.device_dir.join("mem_info_vram_total")),
+            })
+            .collect();
+        trace!("REALTIME GPU 获取成功: {gpus:?}");
+        gpus
+    }
+}
+
+/// 枚举 `cardN` 设备，同一设备的多个节点只保留一个
+fn detect_gpus(drm_root: &Path) -> Vec<GpuDevice> {
+    let Ok(entries) = fs::read_dir(drm_root) else {
+        return Vec::new();
+    };
+    let mut cards: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("card"))
+                .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .collect();
+    cards.sort();
+
+    let mut gpus: Vec<GpuDevice> = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for card in cards {
+        let device_dir = card.join("device");
+        let canonical = fs::canonicalize(&device_dir).unwrap_or_else(|_| device_dir.clone());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        let Some(name) = device_name(&device_dir) else {
+            continue;
+        };
+        seen.push(canonical);
+        gpus.push(GpuDevice { name, device_dir });
+    }
+    gpus
+}
+
+/// PCI 设备按 ID 查表，SoC 集成显卡等非 PCI 设备使用驱动名
+fn device_name(device_dir: &Path) -> Option<String> {
+    let vendor = read_hex_u16(&device_dir.join("vendor"));
+    let device = read_hex_u16(&device_dir.join("device"));
+    if let (Some(vendor), Some(device)) = (vendor, device) {
+        return Some(pci_name(vendor, device));
+    }
+
+    let driver = fs::read_link(device_dir.join("driver")).ok()?;
+    Some(driver.file_name()?.to_string_lossy().into_owned())
+}
+
+fn pci_name(vendor: u16, device: u16) -> String {
+    let vendor_name = PCI_VENDORS
+        .iter()
+        .find(|(id, _)| *id == vendor)
+        .map(|(_, name)| *name);
+    let device_name = PCI_DEVICES
+        .iter()
+        .find(|(v, d, _)| *v == vendor && *d == device)
+        .map(|(_, _, name)| *name);
+
+    match (vendor_name, device_name) {
+        (Some(vendor_name), Some(device_name)) => format!("{vendor_name} {device_name}"),
+        (Some(vendor_name), None) => format!("{vendor_name} GPU [{vendor:04x}:{device:04x}]"),
+        (None, _) => format!("GPU [{vendor:04x}:{device:04x}]"),
+    }
+}
+
+fn read_hex_u16(path: &Path) -> Option<u16> {
+    let content = fs::read_to_string(path).ok()?;
+    let content = content.trim();
+    u16::from_str_radix(content.strip_prefix("0x").unwrap_or(content), 16).ok()
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn card(root: &Path, card: &str, device: &str) {
+        fs::create_dir_all(root.join("drm").join(card)).unwrap();
+        symlink(
+            root.join(device),
+            root.join("drm").join(card).join("device"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reads_names_and_amdgpu_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, "pci/0000:03:00.0/vendor", "0x1002\n");
+        write(root, "pci/0000:03:00.0/device", "0x740f\n");
+        write(root, "pci/0000:03:00.0/gpu_busy_percent", "37\n");
+        write(root, "pci/0000:03:00.0/mem_info_vram_used", "1073741824\n");
+        write(
+            root,
+            "pci/0000:03:00.0/mem_info_vram_total",
+            "68702699520\n",
+        );
+        write(root, "pci/0000:00:02.0/vendor", "0x8086\n");
+        write(root, "pci/0000:00:02.0/device", "0xffff\n");
+        write(root, "platform/gpu/uevent", "\n");
+        symlink(root.join("drivers/vc4"), root.join("platform/gpu/driver")).unwrap();
+
+        card(root, "card0", "pci/0000:00:02.0");
+        card(root, "card1", "pci/0000:03:00.0");
+        // 同一设备的另一个节点只保留一个
+        card(root, "card2", "pci/0000:03:00.0");
+        card(root, "card3", "platform/gpu");
+        card(root, "card1-DP-1", "pci/0000:03:00.0");
+        card(root, "renderD128", "pci/0000:03:00.0");
+
+        let gpus = GpuCollector::with_root(&root.join("drm")).collect();
+        let summary: Vec<_> = gpus
+            .iter()
+            .map(|gpu| (gpu.name.as_str(), gpu.usage, gpu.mem_used, gpu.mem_total))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Intel GPU [8086:ffff]", None, None, None),
+                (
+                    "AMD Instinct MI210",
+                    Some(37.0),
+                    Some(1_073_741_824),
+                    Some(68_702_699_520)
+                ),
+                ("vc4", None, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn pci_name_falls_back_to_ids() {
+        assert_eq!(pci_name(0x10de, 0x2330), "NVIDIA H100 SXM5 80GB");
+        assert_eq!(pci_name(0x10de, 0x0001), "NVIDIA GPU [10de:0001]");
+        assert_eq!(pci_name(0xabcd, 0x0001), "GPU [abcd:0001]");
+    }
+
+    #[test]
+    fn missing_drm_yields_no_gpus() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(GpuCollector::with_root(dir.path()).collect().is_empty());
+    }
+}
@@ -30,7 +30,8 @@ pub async fn ip_ipinfo() -> IPInfo {
     let ipv4: JoinHandle<Option<Ipv4Addr>> = tokio::spawn(async move {
         #[cfg(feature = "ureq-support")]
         let body = {
-            let Ok(mut resp) = ureq::get("https://api.myip.com")
+            let Ok(mut resp) = crate::utils::create_ureq_agent(None)
+                .get("https://api.myip.com")
                 .header("User-Agent", "curl/8.7.1")
                 .config()
                 .timeout_global(Some(Duration::from_secs(5)))
@@ -74,7 +75,8 @@ pub async fn ip_ipinfo() -> IPInfo {
     let ipv6: JoinHandle<Option<Ipv6Addr>> = tokio::spawn(async move {
         #[cfg(feature = "ureq-support")]
         let body = {
-            let Ok(mut resp) = ureq::get("https://v6.myip.la/json")
+            let Ok(mut resp) = crate::utils::create_ureq_agent(None)
+                .get("https://v6.myip.la/json")
                 .header("User-Agent", "curl/8.7.1")
                 .config()
                 .timeout_global(Some(Duration::from_secs(5)))
@@ -128,7 +130,8 @@ pub async fn ip_cloudflare() -> IPInfo {
     let ipv4: JoinHandle<Option<Ipv4Addr>> = tokio::spawn(async move {
         #[cfg(feature = "ureq-support")]
         let body = {
-            let Ok(mut resp) = ureq::get("https://www.cloudflare.com/cdn-cgi/trace")
+            let Ok(mut resp) = crate::utils::create_ureq_agent(None)
+                .get("https://www.cloudflare.com/cdn-cgi/trace")
                 .header("User-Agent", "curl/8.7.1")
                 .config()
                 .timeout_global(Some(Duration::from_secs(5)))
@@ -173,7 +176,8 @@ pub async fn ip_cloudflare() -> IPInfo {
     let ipv6: JoinHandle<Option<Ipv6Addr>> = tokio::spawn(async move {
         #[cfg(feature = "ureq-support")]
         let body = {
-            let Ok(mut resp) = ureq::get("https://www.cloudflare.com/cdn-cgi/trace")
+            let Ok(mut resp) = crate::utils::create_ureq_agent(None)
+                .get("https://www.cloudflare.com/cdn-cgi/trace")
                 .header("User-Agent", "curl/8.7.1")
                 .config()
                 .timeout_global(Some(Duration::from_secs(5)))
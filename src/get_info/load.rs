@@ -1,5 +1,6 @@
-use crate::data_struct::Load;
+use crate::data_struct::{Load, Pressure, PressureResource, PressureStall};
 use log::trace;
+use std::fs;
 
 #[cfg(not(target_os = "windows"))]
 pub fn realtime_load() -> Load {
@@ -10,7 +11,7 @@ pub fn realtime_load() -> Load {
         load5: load.five,
         load15: load.fifteen,
     };
-    trace!("REALTIME LOAD 获取成功: {:?}", load_info);
+    trace!("REALTIME LOAD 获取成功: {load_info:?}");
     load_info
 }
 
@@ -24,3 +25,98 @@ pub fn realtime_load() -> Load {
     trace!("REALTIME LOAD 获取成功: {load_info:?}");
     load_info
 }
+
+/// 读取 `/proc/pressure` 中 CPU、内存与 IO 的压力阻塞信息，内核不支持 PSI 时返回 None
+pub fn realtime_pressure() -> Option<Pressure> {
+    let pressure = Pressure {
+        cpu: read_pressure("cpu"),
+        memory: read_pressure("memory"),
+        io: read_pressure("io"),
+    };
+    if pressure.cpu.is_none() && pressure.memory.is_none() && pressure.io.is_none() {
+        return None;
+    }
+    trace!("REALTIME PRESSURE 获取成功: {pressure:?}");
+    Some(pressure)
+}
+
+fn read_pressure(resource: &str) -> Option<PressureResource> {
+    parse_pressure(&fs::read_to_string(format!("/proc/pressure/{resource}")).ok()?)
+}
+
+/// 格式为 `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`，5.13 之前的内核中 cpu 没有 full 行
+fn parse_pressure(content: &str) -> Option<PressureResource> {
+    let mut some = None;
+    let mut full = None;
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let target = match fields.next() {
+            Some("some") => &mut some,
+            Some("full") => &mut full,
+            _ => continue,
+        };
+        let mut stall = PressureStall {
+            avg10: 0.0,
+            avg60: 0.0,
+            avg300: 0.0,
+            total: 0,
+        };
+        for (key, value) in fields.filter_map(|field| field.split_once('=')) {
+            match key {
+                "avg10" => stall.avg10 = value.parse().unwrap_or(0.0),
+                "avg60" => stall.avg60 = value.parse().unwrap_or(0.0),
+                "avg300" => stall.avg300 = value.parse().unwrap_or(0.0),
+                "total" => stall.total = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+        *target = Some(stall);
+    }
+
+    Some(PressureResource { some: some?, full })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_stall(stall: &PressureStall, avg10: f64, avg60: f64, avg300: f64, total: u64) {
+        assert_eq!(
+            [stall.avg10, stall.avg60, stall.avg300].map(f64::to_bits),
+            [avg10, avg60, avg300].map(f64::to_bits)
+        );
+        assert_eq!(stall.total, total);
+    }
+
+    #[test]
+    fn parses_some_and_full_lines() {
+        let pressure = parse_pressure(
+            "some avg10=1.25 avg60=0.50 avg300=0.10 total=123456\n\
+             full avg10=0.75 avg60=0.25 avg300=0.05 total=65432\n",
+        )
+        .unwrap();
+        assert_stall(&pressure.some, 1.25, 0.5, 0.1, 123_456);
+        assert_stall(&pressure.full.unwrap(), 0.75, 0.25, 0.05, 65432);
+    }
+
+    #[test]
+    fn cpu_without_full_line_on_old_kernels() {
+        let pressure = parse_pressure("some avg10=0.00 avg60=0.00 avg300=0.00 total=42\n").unwrap();
+        assert_stall(&pressure.some, 0.0, 0.0, 0.0, 42);
+        assert!(pressure.full.is_none());
+    }
+
+    #[test]
+    fn missing_some_line_is_rejected() {
+        assert!(parse_pressure("").is_none());
+        assert!(parse_pressure("full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").is_none());
+    }
+
+    #[test]
+    fn unknown_and_invalid_fields_default_to_zero() {
+        let pressure =
+            parse_pressure("some avg10=abc avg30=9.9 avg300=2.00 total=-1 extra\nnoise line\n")
+                .unwrap();
+        assert_stall(&pressure.some, 0.0, 0.0, 2.0, 0);
+    }
+}
@@ -1,5 +1,10 @@
+use crate::command_parser::{Args, DiskRule};
 use crate::data_struct::{Disk, Ram, Swap};
+use crate::get_info::filter::wildcard_match;
 use log::trace;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use sysinfo::{Disks, System};
 
 #[derive(Debug)]
@@ -54,38 +59,186 @@ pub fn realtime_disk(disk: &Disks) -> Disk {
         used_disk += disk.total_space() - disk.available_space();
     }
 
-    let disk_info = Disk { used: used_disk };
+    let disk_info = Disk {
+        used: used_disk,
+        io: None,
+    };
     trace!("REALTIME DISK 获取成功: {disk_info:?}");
     disk_info
 }
 
+/// 默认统计的文件系统
+const DEFAULT_FILESYSTEMS: [&str; 16] = [
+    "apfs",
+    "ext4",
+    "ext3",
+    "ext2",
+    "f2fs",
+    "reiserfs",
+    "jfs",
+    "btrfs",
+    "fuseblk",
+    "zfs",
+    "simfs",
+    "ntfs",
+    "fat32",
+    "exfat",
+    "xfs",
+    "fuse.rclone",
+];
+
+#[derive(Debug, Default)]
+struct DiskFilter {
+    include: Vec<DiskRule>,
+    exclude: Vec<DiskRule>,
+}
+
+static DISK_FILTER: OnceLock<DiskFilter> = OnceLock::new();
+
+/// 启动时读取磁盘统计规则，修改后需要重启才能生效 (总容量只在启动时上报一次)
+pub fn init_disk_filter(args: &Args) {
+    let _ = DISK_FILTER.set(DiskFilter {
+        include: args.disk_include.clone(),
+        exclude: args.disk_exclude.clone(),
+    });
+}
+
+/// 挂载点与设备名 -> 去重使用的设备标识，磁盘列表变化时只为新出现的挂载点计算
+static DEVICE_KEYS: Mutex<Vec<(PathBuf, String, String)>> = Mutex::new(Vec::new());
+
+/// 按文件系统与包含、排除规则筛选磁盘，同一设备的多个挂载点 (bind mount、btrfs 子卷等) 只统计一次
 pub fn filter_disks(disks: &Disks) -> Vec<&sysinfo::Disk> {
-    let allowed = [
-        "apfs",
-        "ext4",
-        "ext3",
-        "ext2",
-        "f2fs",
-        "reiserfs",
-        "jfs",
-        "btrfs",
-        "fuseblk",
-        "zfs",
-        "simfs",
-        "ntfs",
-        "fat32",
-        "exfat",
-        "xfs",
-        "fuse.rclone",
-    ];
-
-    let filtered: Vec<&sysinfo::Disk> = disks
+    let filter = DISK_FILTER.get_or_init(DiskFilter::default);
+
+    let selected: Vec<&sysinfo::Disk> = disks
         .iter() // 返回 &Disk
         .filter(|disk| {
             let fs = disk.file_system().to_string_lossy();
-            allowed.contains(&fs.as_ref())
+            let matches = |rule: &DiskRule| rule_matches(rule, disk);
+            if filter.exclude.iter().any(matches) {
+                return false;
+            }
+            DEFAULT_FILESYSTEMS.contains(&fs.as_ref()) || filter.include.iter().any(matches)
         })
         .collect();
 
-    filtered
+    let mut cache = DEVICE_KEYS.lock().unwrap();
+    let mut keys = Vec::with_capacity(selected.len());
+    for disk in &selected {
+        let mount_point = disk.mount_point();
+        let name = disk.name().to_string_lossy();
+        let key = match cache
+            .iter()
+            .find(|(mount, cached_name, _)| mount == mount_point && *cached_name == name)
+        {
+            Some((_, _, key)) => key.clone(),
+            None => device_key(&name, &disk.file_system().to_string_lossy(), mount_point),
+        };
+        keys.push((mount_point.to_path_buf(), name.into_owned(), key));
+    }
+
+    let mut seen = HashSet::new();
+    let deduplicated = selected
+        .into_iter()
+        .zip(&keys)
+        .filter(|(disk, (_, _, key))| {
+            if seen.insert(key) {
+                true
+            } else {
+                trace!(
+                    "跳过重复挂载的磁盘 {} ({key})",
+                    disk.mount_point().display()
+                );
+                false
+            }
+        })
+        .map(|(disk, _)| disk)
+        .collect();
+    *cache = keys;
+    deduplicated
+}
+
+fn rule_matches(rule: &DiskRule, disk: &sysinfo::Disk) -> bool {
+    match rule {
+        DiskRule::FileSystem(pattern) => {
+            wildcard_match(pattern, &disk.file_system().to_string_lossy())
+        }
+        DiskRule::MountPoint(pattern) => {
+            wildcard_match(pattern, &disk.mount_point().to_string_lossy())
+        }
+        DiskRule::Device(pattern) => wildcard_match(pattern, &disk.name().to_string_lossy()),
+    }
+}
+
+/// 网络文件系统，按导出路径 (如 `server:/export`) 区分，不访问挂载点以免服务器无响应时阻塞
+///
+/// Linux 下 sysinfo 不会列出网络文件系统 (未启用 `linux-netdevs`)，采集时的 statvfs 同样可能阻塞
+const NETWORK_FILESYSTEMS: [&str; 8] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ceph",
+    "glusterfs",
+    "fuse.sshfs",
+];
+
+/// 块设备按设备路径区分；overlay 等没有块设备的本地文件系统按挂载点所在的设备号区分
+fn device_key(name: &str, file_system: &str, mount_point: &Path) -> String {
+    if name.starts_with("/dev/") {
+        return name.to_string();
+    }
+    if NETWORK_FILESYSTEMS.contains(&file_system) {
+        return format!("net:{name}");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = std::fs::metadata(mount_point) {
+            return format!("dev:{}", metadata.dev());
+        }
+    }
+
+    format!("{name}@{}", mount_point.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_devices_are_keyed_by_device_path() {
+        assert_eq!(device_key("/dev/sda1", "ext4", Path::new("/")), "/dev/sda1");
+    }
+
+    #[test]
+    fn network_filesystems_do_not_touch_the_mount_point() {
+        let missing = Path::new("/nonexistent/komari/nfs");
+        assert_eq!(
+            device_key("server:/export", "nfs4", missing),
+            "net:server:/export"
+        );
+        assert_eq!(
+            device_key("//host/share", "cifs", missing),
+            "net://host/share"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn other_filesystems_share_a_key_per_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+
+        let key = device_key("overlay", "overlay", dir.path());
+        assert!(key.starts_with("dev:"));
+        assert_eq!(device_key("overlay", "overlay", &nested), key);
+        assert_eq!(
+            device_key("none", "tmpfs", Path::new("/nonexistent/komari")),
+            "none@/nonexistent/komari"
+        );
+    }
 }
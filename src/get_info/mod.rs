@@ -1,13 +1,227 @@
-use log::trace;
+use crate::command_parser::{Args, TrafficReport};
+use crate::data_struct::RealTimeInfo;
+use crate::get_info::conntrack::ConntrackCollector;
+use crate::get_info::cpu::CpuDetailCollector;
+use crate::get_info::diskstats::DiskStatsCollector;
+use crate::get_info::filter::NameFilter;
+use crate::get_info::gpu::GpuCollector;
+use crate::get_info::load::realtime_pressure;
+use crate::get_info::network::ConnectionStats;
+use crate::get_info::network::connections::ConnectionDetailCollector;
+use crate::get_info::network::interfaces::{InterfaceFilter, interface_breakdown};
+use crate::get_info::sensors::SensorCollector;
+use crate::traffic_history::TrafficHistory;
+use crate::traffic_ledger::TrafficLedger;
+use log::{trace, warn};
 use std::fs;
-use sysinfo::System;
+use sysinfo::{
+    CpuRefreshKind, DiskRefreshKind, Disks, MemoryRefreshKind, Networks, RefreshKind, System,
+};
 
+pub mod conntrack;
 pub mod cpu;
+pub mod diskstats;
+pub mod filter;
+pub mod gpu;
 pub mod ip;
 pub mod load;
 pub mod mem;
 pub mod network;
 pub mod os;
+pub mod sensors;
+
+/// 持有 sysinfo 的各项状态，每个周期刷新一次并生成 `RealTimeInfo`
+pub struct Collector {
+    pub sysinfo_sys: System,
+    networks: Networks,
+    disks: Disks,
+    cpu_detail: Option<CpuDetailCollector>,
+    sensors: Option<SensorCollector>,
+    gpu: Option<GpuCollector>,
+    disk_io: Option<DiskStatsCollector>,
+    network_filter: InterfaceFilter,
+    net_interfaces: bool,
+    traffic_ledger: Option<TrafficLedger>,
+    traffic_report: TrafficReport,
+    traffic_history: Option<TrafficHistory>,
+    connection_detail: Option<ConnectionDetailCollector>,
+    connection_stats: ConnectionStats,
+    conntrack: Option<ConntrackCollector>,
+    pressure: bool,
+}
+
+impl Collector {
+    pub fn new(args: &Args) -> Self {
+        let mut sysinfo_sys = System::new();
+        sysinfo_sys.refresh_cpu_list(
+            CpuRefreshKind::nothing()
+                .without_cpu_usage()
+                .without_frequency(),
+        );
+        sysinfo_sys.refresh_memory_specifics(MemoryRefreshKind::everything());
+
+        let mut collector = Self {
+            sysinfo_sys,
+            networks: Networks::new_with_refreshed_list(),
+            disks: Disks::new(),
+            cpu_detail: None,
+            sensors: None,
+            gpu: None,
+            disk_io: None,
+            network_filter: InterfaceFilter::new(args),
+            net_interfaces: false,
+            traffic_ledger: args
+                .traffic_ledger_file
+                .as_deref()
+                .map(|path| TrafficLedger::new(args, path)),
+            traffic_report: TrafficReport::Raw,
+            traffic_history: args
+                .traffic_history_file
+                .as_deref()
+                .map(|path| TrafficHistory::new(args, path)),
+            connection_detail: None,
+            connection_stats: ConnectionStats::default(),
+            conntrack: None,
+            pressure: false,
+        };
+        collector.reconfigure(args);
+        collector
+    }
+
+    /// 按参数启用或关闭可选段，配置重新加载后调用
+    pub fn reconfigure(&mut self, args: &Args) {
+        if args.cpu_detail != self.cpu_detail.is_some() {
+            self.cpu_detail = args
+                .cpu_detail
+                .then(|| CpuDetailCollector::new(&self.sysinfo_sys));
+        }
+        // 重新枚举传感器，使新的包含与排除列表生效
+        self.sensors = args.sensors.then(|| {
+            SensorCollector::new(&NameFilter::new(&args.sensor_include, &args.sensor_exclude))
+        });
+        if args.gpu != self.gpu.is_some() {
+            self.gpu = args.gpu.then(GpuCollector::new);
+        }
+        self.disk_io = args
+            .disk_io
+            .then(|| DiskStatsCollector::new(&args.disk_io_devices));
+        self.network_filter = InterfaceFilter::new(args);
+        self.net_interfaces = args.net_interfaces;
+        if let Some(traffic_ledger) = &mut self.traffic_ledger {
+            traffic_ledger.reconfigure(args);
+        } else if args.traffic_report != TrafficReport::Raw {
+            warn!(
+                "未设置 --traffic-ledger-file，--traffic-report {} 不会生效",
+                args.traffic_report
+            );
+        }
+        self.traffic_report = args.traffic_report.clone();
+        if let Some(traffic_history) = &mut self.traffic_history {
+            traffic_history.reconfigure(args);
+        }
+        if args.connection_detail != self.connection_detail.is_some() {
+            self.connection_detail = args
+                .connection_detail
+                .then(|| ConnectionDetailCollector::new(args.top_talkers));
+        }
+        if let Some(connection_detail) = &mut self.connection_detail {
+            connection_detail.set_top_talkers(args.top_talkers);
+        }
+        if args.conntrack != self.conntrack.is_some() {
+            self.conntrack = args
+                .conntrack
+                .then(|| ConntrackCollector::new(args.conntrack_alert_percent));
+        }
+        if let Some(conntrack) = &mut self.conntrack {
+            conntrack.set_alert_percent(args.conntrack_alert_percent);
+        }
+        if args.pressure && !self.pressure && fs::metadata("/proc/pressure").is_err() {
+            warn!("当前内核不支持 PSI (/proc/pressure 不存在)，将不会上报压力阻塞信息");
+        }
+        self.pressure = args.pressure;
+    }
+
+    /// 退出前将流量账本与流量历史写入磁盘
+    pub fn flush(&mut self) {
+        if let Some(traffic_ledger) = &mut self.traffic_ledger {
+            traffic_ledger.save();
+        }
+        if let Some(traffic_history) = &mut self.traffic_history {
+            traffic_history.save();
+        }
+    }
+
+    pub fn collect(&mut self) -> RealTimeInfo {
+        // 频率仅在上报核心详情时需要，读取 cpufreq 的开销不可忽略
+        let cpu_refresh = if self.cpu_detail.is_some() {
+            CpuRefreshKind::everything()
+        } else {
+            CpuRefreshKind::everything().without_frequency()
+        };
+        self.sysinfo_sys.refresh_specifics(
+            RefreshKind::nothing()
+                .with_cpu(cpu_refresh)
+                .with_memory(MemoryRefreshKind::everything()),
+        );
+        self.networks.refresh(true);
+        self.disks
+            .refresh_specifics(true, DiskRefreshKind::nothing().with_storage());
+
+        let selected_networks = self.network_filter.select(&self.networks);
+        // 启用连接详情时连接数由详情的查询结果得出，每个周期只查询一遍 sock_diag
+        let (connection_detail, connections) = match &mut self.connection_detail {
+            Some(detail) => {
+                let (detail, connections) = detail.collect(&mut self.connection_stats);
+                (Some(detail), connections)
+            }
+            None => (None, self.connection_stats.collect()),
+        };
+        let mut real_time = RealTimeInfo::build(
+            &self.sysinfo_sys,
+            &selected_networks,
+            &self.disks,
+            connections,
+        );
+        real_time.connection_detail = connection_detail;
+        if let Some(traffic_ledger) = &mut self.traffic_ledger {
+            traffic_ledger.record(&selected_networks);
+            match self.traffic_report {
+                TrafficReport::Raw => {}
+                TrafficReport::Cycle => {
+                    let usage = traffic_ledger.usage();
+                    real_time.network.total_up = usage.cycle_up;
+                    real_time.network.total_down = usage.cycle_down;
+                }
+                TrafficReport::Both => real_time.traffic = Some(traffic_ledger.usage()),
+            }
+        }
+        if let Some(traffic_history) = &mut self.traffic_history {
+            traffic_history.record(&selected_networks);
+        }
+        real_time.conntrack = self
+            .conntrack
+            .as_mut()
+            .and_then(ConntrackCollector::collect);
+        if self.pressure {
+            real_time.pressure = realtime_pressure();
+        }
+        if self.net_interfaces {
+            real_time.network_interfaces = Some(interface_breakdown(&selected_networks));
+        }
+        real_time.cpu_detail = self
+            .cpu_detail
+            .as_mut()
+            .map(|cpu_detail| cpu_detail.collect(&self.sysinfo_sys));
+        real_time.sensors = self.sensors.as_ref().map(SensorCollector::collect);
+        real_time.gpu = self.gpu.as_ref().map(GpuCollector::collect);
+        if let Some(disk_io) = &mut self.disk_io {
+            let (devices, total) = disk_io.collect();
+            real_time.disk.io = Some(total);
+            real_time.disk_io = Some(devices);
+        }
+        real_time
+    }
+}
 
 pub fn realtime_uptime() -> u64 {
     let uptime = System::uptime();
@@ -0,0 +1,277 @@
+use crate::data_struct::{ConnectionDetail, Connections, ListeningSocket, TcpStates, TopTalker};
+use crate::get_info::network::ConnectionStats;
+#[cfg(target_os = "linux")]
+use crate::get_info::network::netlink::InetSocket;
+#[cfg(target_os = "linux")]
+use crate::get_info::network::per_second;
+use log::trace;
+#[cfg(not(target_os = "linux"))]
+use log::warn;
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// 监听端口所属进程未知时重新扫描 /proc 的最短间隔
+const OWNER_RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 内核中的 TCP 状态编号 (`include/net/tcp_states.h`)
+#[cfg(target_os = "linux")]
+mod tcp_state {
+    pub const ESTABLISHED: u8 = 1;
+    pub const SYN_SENT: u8 = 2;
+    pub const SYN_RECV: u8 = 3;
+    pub const FIN_WAIT1: u8 = 4;
+    pub const FIN_WAIT2: u8 = 5;
+    pub const TIME_WAIT: u8 = 6;
+    pub const CLOSE: u8 = 7;
+    pub const CLOSE_WAIT: u8 = 8;
+    pub const LAST_ACK: u8 = 9;
+    pub const LISTEN: u8 = 10;
+    pub const CLOSING: u8 = 11;
+    /// 半连接队列中的请求，计入 `SYN_RECV`
+    pub const NEW_SYN_RECV: u8 = 12;
+}
+
+/// 通过 `sock_diag` 汇总 TCP 各状态的连接数、监听端口与连接最多的远程地址
+///
+/// 远程地址的速率由每个 TCP 连接两次采集之间累计字节数的差值得出，回环地址不参与排名
+#[derive(Debug)]
+pub struct ConnectionDetailCollector {
+    top_talkers: usize,
+    /// 上次采集时每个 TCP 连接 (按 cookie 区分) 的累计发送与接收字节数
+    previous_bytes: Option<HashMap<u64, (u64, u64)>>,
+    /// 套接字 inode -> (pid, 进程名)
+    owners: HashMap<u32, (u32, String)>,
+    owners_scanned_at: Option<Instant>,
+}
+
+impl ConnectionDetailCollector {
+    pub fn new(top_talkers: usize) -> Self {
+        #[cfg(not(target_os = "linux"))]
+        warn!("当前平台不支持 --connection-detail，连接详情将为空");
+
+        Self {
+            top_talkers,
+            previous_bytes: None,
+            owners: HashMap::new(),
+            owners_scanned_at: None,
+        }
+    }
+
+    pub fn set_top_talkers(&mut self, top_talkers: usize) {
+        self.top_talkers = top_talkers;
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn collect(&mut self, stats: &mut ConnectionStats) -> (ConnectionDetail, Connections) {
+        let detail = ConnectionDetail {
+            tcp_states: TcpStates::default(),
+            listening: Vec::new(),
+            top_talkers: Vec::new(),
+        };
+        (detail, stats.collect())
+    }
+
+    /// 连接详情与 TCP/UDP 连接数，连接数由同一次查询的结果得出，无需再单独统计
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self, stats: &mut ConnectionStats) -> (ConnectionDetail, Connections) {
+        let all_states = u32::MAX;
+        let mut tcp = Vec::new();
+        let mut udp = Vec::new();
+        for family in [libc::AF_INET as u8, libc::AF_INET6 as u8] {
+            if let Some(sockets) =
+                stats.query(|client| client.dump(family, libc::IPPROTO_TCP as u8, all_states, true))
+            {
+                tcp.extend(sockets);
+            }
+            if let Some(sockets) = stats
+                .query(|client| client.dump(family, libc::IPPROTO_UDP as u8, all_states, false))
+            {
+                udp.extend(sockets);
+            }
+        }
+
+        let tcp_states = count_states(&tcp);
+        let mut listening: Vec<ListeningSocket> = tcp
+            .iter()
+            .filter(|socket| socket.state == tcp_state::LISTEN)
+            .map(|socket| listening_socket("tcp", socket))
+            .chain(
+                udp.iter()
+                    // 未连接的 UDP 套接字即为监听中的端口
+                    .filter(|socket| socket.state == tcp_state::CLOSE && socket.remote.port() == 0)
+                    .map(|socket| listening_socket("udp", socket)),
+            )
+            .collect();
+        self.resolve_owners(&mut listening);
+        listening.sort_by(|a, b| {
+            (&a.protocol, a.port, &a.address).cmp(&(&b.protocol, b.port, &b.address))
+        });
+
+        // 与 ConnectionStats::collect 一致: TCP 只统计 ESTABLISHED 状态，UDP 统计全部
+        let connections = Connections {
+            tcp: tcp_states.established,
+            udp: udp.len() as u64,
+        };
+        trace!("REALTIME CONNECTIONS 获取成功: {connections:?}");
+
+        let detail = ConnectionDetail {
+            tcp_states,
+            listening,
+            top_talkers: self.top_talkers(&tcp),
+        };
+        trace!("REALTIME CONNECTION DETAIL 获取成功: {detail:?}");
+        (detail, connections)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn top_talkers(&mut self, tcp: &[InetSocket]) -> Vec<TopTalker> {
+        let previous = self.previous_bytes.take();
+        let mut current = HashMap::with_capacity(tcp.len());
+        let mut talkers: HashMap<IpAddr, TopTalker> = HashMap::new();
+
+        for socket in tcp {
+            let remote = socket.remote.ip();
+            if socket.state == tcp_state::LISTEN || remote.is_loopback() || remote.is_unspecified()
+            {
+                continue;
+            }
+            let talker = talkers.entry(remote).or_insert_with(|| TopTalker {
+                address: remote.to_string(),
+                connections: 0,
+                up: 0,
+                down: 0,
+            });
+            talker.connections += 1;
+
+            let Some((sent, received)) = socket.bytes else {
+                continue;
+            };
+            current.insert(socket.cookie, (sent, received));
+            // 首次采集没有基准；本周期新建的连接全部计入
+            if let Some(previous) = &previous {
+                let (sent_before, received_before) =
+                    previous.get(&socket.cookie).copied().unwrap_or_default();
+                talker.up += per_second(sent.saturating_sub(sent_before));
+                talker.down += per_second(received.saturating_sub(received_before));
+            }
+        }
+        self.previous_bytes = Some(current);
+
+        let mut talkers: Vec<TopTalker> = talkers.into_values().collect();
+        talkers.sort_by(|a, b| {
+            (b.up + b.down, b.connections, &a.address).cmp(&(
+                a.up + a.down,
+                a.connections,
+                &b.address,
+            ))
+        });
+        talkers.truncate(self.top_talkers);
+        talkers
+    }
+
+    /// 从缓存中查找监听端口所属的进程，缓存缺失时按间隔重新扫描 /proc
+    #[cfg(target_os = "linux")]
+    fn resolve_owners(&mut self, listening: &mut [ListeningSocket]) {
+        let missing = listening
+            .iter()
+            .any(|socket| !self.owners.contains_key(&socket.inode));
+        let rescan_due = self
+            .owners_scanned_at
+            .is_none_or(|at| at.elapsed() >= OWNER_RESCAN_INTERVAL);
+        if missing && rescan_due {
+            self.owners = scan_socket_owners();
+            self.owners_scanned_at = Some(Instant::now());
+        }
+
+        for socket in listening {
+            if let Some((pid, process)) = self.owners.get(&socket.inode) {
+                socket.pid = Some(*pid);
+                socket.process = Some(process.clone());
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn count_states(tcp: &[InetSocket]) -> TcpStates {
+    let mut states = TcpStates::default();
+    for socket in tcp {
+        let counter = match socket.state {
+            tcp_state::ESTABLISHED => &mut states.established,
+            tcp_state::SYN_SENT => &mut states.syn_sent,
+            tcp_state::SYN_RECV | tcp_state::NEW_SYN_RECV => &mut states.syn_recv,
+            tcp_state::FIN_WAIT1 => &mut states.fin_wait1,
+            tcp_state::FIN_WAIT2 => &mut states.fin_wait2,
+            tcp_state::TIME_WAIT => &mut states.time_wait,
+            tcp_state::CLOSE => &mut states.close,
+            tcp_state::CLOSE_WAIT => &mut states.close_wait,
+            tcp_state::LAST_ACK => &mut states.last_ack,
+            tcp_state::LISTEN => &mut states.listen,
+            tcp_state::CLOSING => &mut states.closing,
+            _ => continue,
+        };
+        *counter += 1;
+    }
+    states
+}
+
+#[cfg(target_os = "linux")]
+fn listening_socket(protocol: &str, socket: &InetSocket) -> ListeningSocket {
+    ListeningSocket {
+        protocol: protocol.to_string(),
+        address: socket.local.ip().to_string(),
+        port: socket.local.port(),
+        inode: socket.inode,
+        pid: None,
+        process: None,
+    }
+}
+
+/// 遍历 /proc/<pid>/fd 中指向 `socket:[inode]` 的链接，无权限读取的进程会被跳过
+#[cfg(target_os = "linux")]
+fn scan_socket_owners() -> HashMap<u32, (u32, String)> {
+    let mut owners = HashMap::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return owners;
+    };
+
+    for process in processes.flatten() {
+        let Some(pid) = process
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let mut name = None;
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let Some(inode) = target
+                .to_str()
+                .and_then(|target| target.strip_prefix("socket:["))
+                .and_then(|target| target.strip_suffix(']'))
+                .and_then(|inode| inode.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let name = name.get_or_insert_with(|| {
+                fs::read_to_string(process.path().join("comm"))
+                    .map(|comm| comm.trim().to_string())
+                    .unwrap_or_default()
+            });
+            owners.entry(inode).or_insert_with(|| (pid, name.clone()));
+        }
+    }
+
+    trace!("扫描到 {} 个套接字的所属进程", owners.len());
+    owners
+}
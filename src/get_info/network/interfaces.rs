@@ -0,0 +1,339 @@
+use crate::command_parser::Args;
+use crate::data_struct::NetworkInterface;
+use crate::get_info::filter::wildcard_match;
+use crate::get_info::network::per_second;
+use log::trace;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use sysinfo::{NetworkData, Networks};
+
+/// 路由表中表示路由可用的标志位 (`RTF_UP`)
+const RTF_UP: u32 = 0x0001;
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// 网卡名称规则，`re:` 开头的按正则表达式匹配，其余按通配符 (无通配符时即完全匹配) 匹配
+#[derive(Debug, Clone)]
+enum InterfacePattern {
+    Glob(String),
+    Regex(Regex),
+}
+
+impl InterfacePattern {
+    fn parse(pattern: &str) -> Result<Self, String> {
+        match pattern.strip_prefix("re:") {
+            Some(regex) => Regex::new(regex)
+                .map(Self::Regex)
+                .map_err(|e| format!("无效的网卡名称正则表达式 {regex}: {e}")),
+            None => Ok(Self::Glob(pattern.to_string())),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => wildcard_match(pattern, name),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// 供命令行解析时提前检查正则表达式是否有效
+pub fn validate_pattern(pattern: &str) -> Result<String, String> {
+    InterfacePattern::parse(pattern).map(|_| pattern.to_string())
+}
+
+/// 选择参与流量统计的网卡
+#[derive(Debug, Clone)]
+pub struct InterfaceFilter {
+    include: Vec<InterfacePattern>,
+    exclude: Vec<InterfacePattern>,
+    default_route_only: bool,
+}
+
+impl InterfaceFilter {
+    pub fn new(args: &Args) -> Self {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|pattern| InterfacePattern::parse(pattern).ok())
+                .collect()
+        };
+
+        Self {
+            include: parse(&args.net_include),
+            exclude: parse(&args.net_exclude),
+            default_route_only: args.net_default_route_only,
+        }
+    }
+
+    /// 按名称排序，保证每次上报的顺序一致
+    ///
+    /// 按名称规则选择时跳过 bond 同样被选中的成员网卡，以及端口同样被选中的网桥，避免流量重复统计
+    pub fn select<'a>(&self, networks: &'a Networks) -> Vec<(&'a str, &'a NetworkData)> {
+        let default_route = if self.default_route_only {
+            default_route_interfaces()
+        } else {
+            HashSet::new()
+        };
+        // 没有默认路由 (或无法读取路由表) 时退回到名称规则，避免流量全部为 0
+        let use_default_route = !default_route.is_empty();
+
+        let mut selected: Vec<(&str, &NetworkData)> = networks
+            .iter()
+            .map(|(name, data)| (name.as_str(), data))
+            .filter(|(name, _)| {
+                if use_default_route {
+                    default_route.contains(*name)
+                } else {
+                    self.allows(name)
+                }
+            })
+            .collect();
+        if !use_default_route {
+            selected = without_duplicates(selected, Path::new(SYS_CLASS_NET));
+        }
+        selected.sort_by_key(|(name, _)| *name);
+
+        trace!(
+            "参与流量统计的网卡: {:?}",
+            selected.iter().map(|(name, _)| name).collect::<Vec<_>>()
+        );
+        selected
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        if self.exclude.iter().any(|pattern| pattern.matches(name)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(name))
+    }
+}
+
+/// 去掉流量会被重复统计的设备
+///
+/// bond 的流量即成员网卡流量之和，保留 bond；网桥只统计本机收发的流量，
+/// 端口上还包含转发给虚拟机的流量，保留端口 (如 `vmbr0` 下的 `eno1`)
+fn without_duplicates<'a, T>(selected: Vec<(&'a str, T)>, net_root: &Path) -> Vec<(&'a str, T)> {
+    let names: HashSet<&str> = selected.iter().map(|(name, _)| *name).collect();
+    let mut skipped = HashSet::new();
+    for name in &names {
+        match upper_device(net_root, name) {
+            Some(UpperDevice::Bond(bond)) if names.contains(bond.as_str()) => {
+                skipped.insert((*name).to_string());
+            }
+            Some(UpperDevice::Bridge(bridge)) if names.contains(bridge.as_str()) => {
+                skipped.insert(bridge);
+            }
+            _ => {}
+        }
+    }
+    selected
+        .into_iter()
+        .filter(|(name, _)| !skipped.contains(*name))
+        .collect()
+}
+
+enum UpperDevice {
+    Bridge(String),
+    Bond(String),
+}
+
+/// 网卡所属的网桥或 bond，Open vSwitch 等其他上级设备不计入
+fn upper_device(net_root: &Path, name: &str) -> Option<UpperDevice> {
+    let master = fs::read_link(net_root.join(name).join("master")).ok()?;
+    let master = master.file_name()?.to_str()?.to_string();
+    let master_dir = net_root.join(&master);
+    if master_dir.join("bridge").exists() {
+        Some(UpperDevice::Bridge(master))
+    } else if master_dir.join("bonding").exists() {
+        Some(UpperDevice::Bond(master))
+    } else {
+        None
+    }
+}
+
+/// 从 `/proc/net/route` 与 `/proc/net/ipv6_route` 中读取拥有默认路由的网卡
+fn default_route_interfaces() -> HashSet<String> {
+    let mut interfaces = HashSet::new();
+
+    // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+    if let Ok(route) = fs::read_to_string("/proc/net/route") {
+        for fields in route
+            .lines()
+            .skip(1)
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        {
+            if fields.len() >= 8
+                && fields[1] == "00000000"
+                && fields[7] == "00000000"
+                && route_is_up(fields[3])
+            {
+                interfaces.insert(fields[0].to_string());
+            }
+        }
+    }
+
+    // Destination PrefixLen Source SourcePrefixLen NextHop Metric RefCnt Use Flags Iface
+    if let Ok(route) = fs::read_to_string("/proc/net/ipv6_route") {
+        for fields in route
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        {
+            if fields.len() >= 10
+                && fields[0].bytes().all(|b| b == b'0')
+                && fields[1] == "00"
+                && route_is_up(fields[8])
+                && fields[9] != "lo"
+            {
+                interfaces.insert(fields[9].to_string());
+            }
+        }
+    }
+
+    interfaces
+}
+
+fn route_is_up(flags: &str) -> bool {
+    u32::from_str_radix(flags, 16).is_ok_and(|flags| flags & RTF_UP != 0)
+}
+
+/// 每块网卡的速率 (Byte/s、包/s) 与累计流量、错误与丢包数
+pub fn interface_breakdown(selected: &[(&str, &NetworkData)]) -> Vec<NetworkInterface> {
+    let interfaces: Vec<NetworkInterface> = selected
+        .iter()
+        .map(|(name, data)| NetworkInterface {
+            name: (*name).to_string(),
+            up: per_second(data.transmitted()),
+            down: per_second(data.received()),
+            total_up: data.total_transmitted(),
+            total_down: data.total_received(),
+            packets_up: per_second(data.packets_transmitted()),
+            packets_down: per_second(data.packets_received()),
+            errors_up: data.total_errors_on_transmitted(),
+            errors_down: data.total_errors_on_received(),
+            drops_up: read_statistic(name, "tx_dropped"),
+            drops_down: read_statistic(name, "rx_dropped"),
+        })
+        .collect();
+
+    trace!("REALTIME NETWORK INTERFACES 获取成功: {interfaces:?}");
+    interfaces
+}
+
+/// sysinfo 不提供丢包数，从 sysfs 读取，其他平台为 None
+fn read_statistic(name: &str, statistic: &str) -> Option<u64> {
+    fs::read_to_string(format!("/sys/class/net/{name}/statistics/{statistic}"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// 在临时目录中模拟 /sys/class/net，`links` 为 (网卡, 上级设备)
+    fn fake_net(bridges: &[&str], bonds: &[&str], links: &[(&str, &str)]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for bridge in bridges {
+            fs::create_dir_all(root.path().join(bridge).join("bridge")).unwrap();
+        }
+        for bond in bonds {
+            fs::create_dir_all(root.path().join(bond).join("bonding")).unwrap();
+        }
+        for (name, master) in links {
+            let dir = root.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            symlink(format!("../{master}"), dir.join("master")).unwrap();
+        }
+        root
+    }
+
+    fn names<'a>(selected: &[(&'a str, ())]) -> Vec<&'a str> {
+        selected.iter().map(|(name, ())| *name).collect()
+    }
+
+    #[test]
+    fn bond_members_are_skipped_when_bond_is_selected() {
+        let root = fake_net(&[], &["bond0"], &[("eth1", "bond0"), ("eth2", "bond0")]);
+        let selected = vec![("bond0", ()), ("eth1", ()), ("eth2", ()), ("wlan0", ())];
+        assert_eq!(
+            names(&without_duplicates(selected, root.path())),
+            ["bond0", "wlan0"]
+        );
+    }
+
+    #[test]
+    fn bridge_is_skipped_when_its_ports_are_selected() {
+        // Proxmox: eno1 上的流量包含转发给虚拟机的部分，vmbr0 只有本机的流量
+        let root = fake_net(
+            &["vmbr0", "br0"],
+            &[],
+            &[("eno1", "vmbr0"), ("eth0", "br0")],
+        );
+        let selected = vec![("vmbr0", ()), ("eno1", ()), ("br0", ()), ("eth0", ())];
+        assert_eq!(
+            names(&without_duplicates(selected, root.path())),
+            ["eno1", "eth0"]
+        );
+    }
+
+    #[test]
+    fn bridge_over_bond_keeps_only_the_bond() {
+        let root = fake_net(
+            &["vmbr0"],
+            &["bond0"],
+            &[("bond0", "vmbr0"), ("eno1", "bond0"), ("eno2", "bond0")],
+        );
+        let selected = vec![("vmbr0", ()), ("bond0", ()), ("eno1", ()), ("eno2", ())];
+        assert_eq!(names(&without_duplicates(selected, root.path())), ["bond0"]);
+    }
+
+    #[test]
+    fn devices_are_kept_when_the_other_side_is_excluded() {
+        let root = fake_net(
+            &["virbr0"],
+            &["bond0"],
+            &[("eth0", "virbr0"), ("eth1", "bond0")],
+        );
+        let selected = vec![("virbr0", ()), ("eth1", ())];
+        assert_eq!(
+            names(&without_duplicates(selected, root.path())),
+            ["virbr0", "eth1"]
+        );
+    }
+
+    #[test]
+    fn non_bridge_masters_are_ignored() {
+        let root = fake_net(&[], &[], &[("eth0", "ovs-system")]);
+        fs::create_dir_all(root.path().join("ovs-system")).unwrap();
+        let selected = vec![("ovs-system", ()), ("eth0", ())];
+        assert_eq!(
+            names(&without_duplicates(selected, root.path())),
+            ["ovs-system", "eth0"]
+        );
+    }
+
+    #[test]
+    fn patterns_match_globs_and_regexes() {
+        assert!(InterfacePattern::parse("br-*").unwrap().matches("br-1a2b"));
+        assert!(!InterfacePattern::parse("br-*").unwrap().matches("br0"));
+        assert!(
+            InterfacePattern::parse("re:^en[ops]\\d")
+                .unwrap()
+                .matches("enp3s0")
+        );
+        assert!(InterfacePattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn route_flags_require_up_bit() {
+        assert!(route_is_up("0003"));
+        assert!(!route_is_up("0002"));
+        assert!(!route_is_up("zz"));
+    }
+}
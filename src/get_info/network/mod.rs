@@ -1,65 +1,101 @@
 use crate::data_struct::{Connections, Network};
 use log::trace;
-use sysinfo::Networks;
+use sysinfo::NetworkData;
+pub mod connections;
+pub mod interfaces;
 #[cfg(target_os = "linux")]
-mod netlink;
+pub mod netlink;
 
 pub static mut DURATION: f64 = 0.0;
-pub fn realtime_network(network: &Networks) -> Network {
+
+/// 将一个上报周期内的增量换算为每秒的速率
+pub fn per_second(value: u64) -> u64 {
+    unsafe { (value as f64 / (DURATION / 1000.0)) as u64 }
+}
+
+/// 汇总经 `InterfaceFilter` 选出的网卡
+pub fn realtime_network(selected: &[(&str, &NetworkData)]) -> Network {
     let mut total_up = 0;
     let mut total_down = 0;
     let mut up = 0;
     let mut down = 0;
 
-    for (name, data) in network {
-        if name.contains("lo")
-            || name.contains("veth")
-            || name.contains("docker")
-            || name.contains("tun")
-            || name.contains("br")
-            || name.contains("tap")
-        {
-            continue;
-        }
+    for (_, data) in selected {
         total_up += data.total_transmitted();
         total_down += data.total_received();
         up += data.transmitted();
         down += data.received();
     }
 
-    unsafe {
-        let network_info = Network {
-            up: (up as f64 / (DURATION / 1000.0)) as u64,
-            down: (down as f64 / (DURATION / 1000.0)) as u64,
-            total_up,
-            total_down,
+    let network_info = Network {
+        up: per_second(up),
+        down: per_second(down),
+        total_up,
+        total_down,
+    };
+    trace!("REALTIME NETWORK 获取成功: {network_info:?}");
+    network_info
+}
+
+/// 统计 TCP 与 UDP 连接数，Linux 下复用同一个 `sock_diag` 套接字
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    #[cfg(target_os = "linux")]
+    client: Option<netlink::SockDiagClient>,
+}
+
+impl ConnectionStats {
+    #[cfg(target_os = "linux")]
+    pub fn collect(&mut self) -> Connections {
+        let mut count = |family: i32, protocol: i32| {
+            self.query(|client| client.count(family as u8, protocol as u8))
+                .unwrap_or(0)
         };
-        trace!("REALTIME NETWORK 获取成功: {network_info:?}");
-        network_info
+        let tcp =
+            count(libc::AF_INET, libc::IPPROTO_TCP) + count(libc::AF_INET6, libc::IPPROTO_TCP);
+        let udp =
+            count(libc::AF_INET, libc::IPPROTO_UDP) + count(libc::AF_INET6, libc::IPPROTO_UDP);
+
+        let connections = Connections { tcp, udp };
+        trace!("REALTIME CONNECTIONS 获取成功: {connections:?}");
+        connections
     }
-}
 
-#[cfg(target_os = "linux")]
-pub fn realtime_connections() -> Connections {
-    use netlink::connections_count_with_protocol;
-    let tcp4 =
-        connections_count_with_protocol(libc::AF_INET as u8, libc::IPPROTO_TCP as u8).unwrap_or(0);
-    let tcp6 =
-        connections_count_with_protocol(libc::AF_INET6 as u8, libc::IPPROTO_TCP as u8).unwrap_or(0);
-    let udp4 =
-        connections_count_with_protocol(libc::AF_INET as u8, libc::IPPROTO_UDP as u8).unwrap_or(0);
-    let udp6 =
-        connections_count_with_protocol(libc::AF_INET6 as u8, libc::IPPROTO_UDP as u8).unwrap_or(0);
-    let connections = Connections {
-        tcp: tcp4 + tcp6,
-        udp: udp4 + udp6,
-    };
-    trace!("REALTIME CONNECTIONS 获取成功: {:?}", connections);
-    connections
+    #[cfg(not(target_os = "linux"))]
+    pub fn collect(&mut self) -> Connections {
+        realtime_connections()
+    }
+
+    /// 按需创建套接字后执行查询；查询失败时关闭套接字，下次查询时重新创建
+    #[cfg(target_os = "linux")]
+    pub fn query<T>(
+        &mut self,
+        query: impl FnOnce(&mut netlink::SockDiagClient) -> std::io::Result<T>,
+    ) -> Option<T> {
+        if self.client.is_none() {
+            match netlink::SockDiagClient::new() {
+                Ok(client) => self.client = Some(client),
+                Err(e) => {
+                    trace!("无法创建 sock_diag 套接字: {e}");
+                    return None;
+                }
+            }
+        }
+
+        let client = self.client.as_mut()?;
+        match query(client) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                trace!("sock_diag 查询失败: {e}");
+                self.client = None;
+                None
+            }
+        }
+    }
 }
 
 #[cfg(target_os = "windows")]
-pub fn realtime_connections() -> Connections {
+fn realtime_connections() -> Connections {
     use netstat2::{ProtocolFlags, ProtocolSocketInfo, iterate_sockets_info_without_pids};
     let proto_flags = ProtocolFlags::TCP | ProtocolFlags::UDP;
 
@@ -87,8 +123,8 @@ pub fn realtime_connections() -> Connections {
 }
 
 #[cfg(not(any(target_os = "linux", target_os = "windows")))]
-pub fn realtime_connections() -> Connections {
+fn realtime_connections() -> Connections {
     let connections = Connections { tcp: 0, udp: 0 };
-    trace!("REALTIME CONNECTIONS 获取成功: {:?}", connections);
+    trace!("REALTIME CONNECTIONS 获取成功: {connections:?}");
     connections
 }
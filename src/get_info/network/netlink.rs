@@ -1,222 +1,679 @@
-use libc::{c_void, close, recvfrom, sendto, sockaddr, sockaddr_nl, socket};
+use libc::{c_void, sockaddr, sockaddr_nl, socklen_t};
+use log::debug;
 use std::io;
-use std::mem::{size_of, zeroed};
-use std::os::fd::RawFd;
-use std::ptr;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 
 const SOCK_DIAG_BY_FAMILY: u16 = 20;
-const ALL_TCP_STATES: u32 = 0xffffffff;
+const ALL_TCP_STATES: u32 = 0xffff_ffff;
 const TCP_ESTABLISHED: u32 = 1;
-const NLMSG_HDRLEN: usize = size_of::<libc::nlmsghdr>();
-
-/// ---- 与内核对齐的 C 结构体 ----
-
-// from linux/inet_diag.h
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct InetDiagSockId {
-    idiag_sport: u16,
-    idiag_dport: u16,
-    idiag_src: [u32; 4], // 足够容纳 IPv6（IPv4 只用 idiag_src[0]）
-    idiag_dst: [u32; 4],
-    idiag_if: u32,
-    idiag_cookie: [u32; 2],
-}
-
-// from linux/inet_diag.h
-#[repr(C)]
-#[derive(Clone, Copy)]
-struct InetDiagReqV2 {
-    family: u8,
-    protocol: u8,
-    ext: u8,
-    pad: u8,
-    states: u32,
-    id: InetDiagSockId,
-}
-
-/// 入口：按协议统计连接消息条数
-pub fn connections_count_with_protocol(family: u8, protocol: u8) -> io::Result<u64> {
-    // 构造 netlink header
-    let hdr = libc::nlmsghdr {
-        nlmsg_len: 0, // 先置 0，serialize 时回填
-        nlmsg_type: SOCK_DIAG_BY_FAMILY,
-        nlmsg_flags: (libc::NLM_F_DUMP | libc::NLM_F_REQUEST) as u16,
-        nlmsg_seq: 0,
-        nlmsg_pid: 0,
-    };
 
-    // 构造 inet_diag_req_v2
-    let mut req = InetDiagReqV2 {
-        family,
-        protocol,
-        ext: 0,
-        pad: 0,
-        states: ALL_TCP_STATES,
-        id: InetDiagSockId {
-            idiag_sport: 0,
-            idiag_dport: 0,
-            idiag_src: [0; 4],
-            idiag_dst: [0; 4],
-            idiag_if: 0,
-            idiag_cookie: [0; 2],
-        },
-    };
+/// `nlmsghdr` 的长度: u32 长度 + u16 类型 + u16 标志 + u32 序号 + u32 端口号
+const NLMSG_HDRLEN: usize = 16;
+/// `inet_diag_req_v2` 的长度: 4 个 u8 + u32 状态掩码 + 48 字节 `inet_diag_sockid`
+const INET_DIAG_REQ_LEN: usize = 56;
+/// `inet_diag_msg` 的长度: 4 字节头部 + 48 字节 `inet_diag_sockid` + 5 个 u32
+const INET_DIAG_MSG_LEN: usize = 72;
+/// 请求附带 `tcp_info` 的扩展属性
+const INET_DIAG_INFO: u16 = 2;
+/// `tcp_info` 中 `tcpi_bytes_acked` 与 `tcpi_bytes_received` 的偏移 (Linux 4.2+)
+const TCPI_BYTES_ACKED_OFFSET: usize = 120;
+const TCPI_BYTES_RECEIVED_OFFSET: usize = 128;
+
+/// 接收缓冲区大小，内核在一次 recv 中尽量填满 (内核建议不小于 8 KiB)
+const RECV_BUFFER_SIZE: usize = 32 * 1024;
+/// 套接字的内核接收缓冲区，套接字数量很多时可以减少 ENOBUFS
+const SOCKET_RCVBUF: libc::c_int = 1024 * 1024;
+/// 内核长时间无响应时放弃本次查询，避免阻塞采集循环
+const RECV_TIMEOUT_SECS: libc::time_t = 5;
+/// 接收缓冲区溢出 (ENOBUFS) 时重新发起查询的次数
+const ENOBUFS_RETRIES: usize = 2;
+
+/// 从 `inet_diag_msg` 中解析出的套接字信息
+#[derive(Debug, Clone)]
+pub struct InetSocket {
+    /// 内核中的 TCP 状态编号，UDP 未连接时为 `TCP_CLOSE`
+    pub state: u8,
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub inode: u32,
+    pub cookie: u64,
+    /// TCP 套接字累计发送 (已确认) 与接收的字节数，仅在请求 `tcp_info` 时存在
+    pub bytes: Option<(u64, u64)>,
+}
 
-    // TCP 只查询 ESTABLISHED 状态的
-    if protocol == libc::IPPROTO_TCP as u8 {
-        req.states = 1 << TCP_ESTABLISHED;
-    }
+/// `nlmsghdr` 中本项目用到的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NetlinkHeader {
+    len: usize,
+    msg_type: u16,
+    flags: u16,
+    seq: u32,
+}
 
-    // 序列化成一条 Netlink 消息（header + payload）
-    let msg = serialize_netlink_message(&hdr, &req)?;
+/// 一批回复中的单条消息
+#[derive(Debug, PartialEq, Eq)]
+enum NetlinkMessage<'a> {
+    SockDiag(&'a [u8]),
+    /// 多段回复结束
+    Done,
+    /// `NLMSG_ERROR`，0 为确认消息，其余为负的 errno
+    Error(i32),
+    /// 其他类型的消息，直接跳过
+    Other,
+}
 
-    // 发送并只统计返回消息条数
-    netlink_inet_diag_only_count(&msg)
+/// 长期持有的 `NETLINK_SOCK_DIAG` 套接字，由采集器持有并在每个周期复用
+///
+/// 每次查询使用新的序号，序号不符的回复 (上次中断的查询残留) 会被丢弃
+#[derive(Debug)]
+pub struct SockDiagClient {
+    fd: OwnedFd,
+    buf: Vec<u8>,
+    seq: u32,
 }
 
-fn netlink_inet_diag_only_count(request: &[u8]) -> io::Result<u64> {
-    let fd = unsafe { socket(libc::AF_NETLINK, libc::SOCK_RAW, libc::NETLINK_SOCK_DIAG) };
-    if fd < 0 {
-        return Err(io::Error::last_os_error());
+impl SockDiagClient {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_SOCK_DIAG,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd 刚由 socket 创建，所有权交给 OwnedFd
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        set_socket_option(&fd, libc::SO_RCVBUF, &SOCKET_RCVBUF)?;
+        let timeout = libc::timeval {
+            tv_sec: RECV_TIMEOUT_SECS,
+            tv_usec: 0,
+        };
+        set_socket_option(&fd, libc::SO_RCVTIMEO, &timeout)?;
+
+        Ok(Self {
+            fd,
+            buf: vec![0; RECV_BUFFER_SIZE],
+            seq: 0,
+        })
+    }
+
+    /// 按协议统计套接字数量，TCP 只统计 ESTABLISHED 状态的连接
+    pub fn count(&mut self, family: u8, protocol: u8) -> io::Result<u64> {
+        let states = if protocol == libc::IPPROTO_TCP as u8 {
+            1 << TCP_ESTABLISHED
+        } else {
+            ALL_TCP_STATES
+        };
+
+        self.with_retry(|client| {
+            let mut count = 0;
+            client.query(family, protocol, states, false, &mut |_| count += 1)?;
+            Ok(count)
+        })
+    }
+
+    /// 列出指定协议在 `states` (按状态编号的位掩码) 中的所有套接字，`with_info` 时附带 TCP 的累计字节数
+    pub fn dump(
+        &mut self,
+        family: u8,
+        protocol: u8,
+        states: u32,
+        with_info: bool,
+    ) -> io::Result<Vec<InetSocket>> {
+        self.with_retry(|client| {
+            let mut sockets = Vec::new();
+            client.query(family, protocol, states, with_info, &mut |payload| {
+                if let Some(socket) = parse_inet_diag_msg(payload) {
+                    sockets.push(socket);
+                }
+            })?;
+            Ok(sockets)
+        })
+    }
+
+    /// 接收缓冲区溢出 (ENOBUFS) 时本次回复已不完整，丢弃结果并重新查询
+    fn with_retry<T>(
+        &mut self,
+        mut query: impl FnMut(&mut Self) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match query(self) {
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) && attempt < ENOBUFS_RETRIES => {
+                    attempt += 1;
+                    debug!("sock_diag 接收缓冲区溢出，重新查询 (第 {attempt} 次)");
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// 发送请求并对每条 `sock_diag` 回复调用 `on_message`，直到多段回复结束
+    fn query(
+        &mut self,
+        family: u8,
+        protocol: u8,
+        states: u32,
+        with_info: bool,
+        on_message: &mut dyn FnMut(&[u8]),
+    ) -> io::Result<()> {
+        self.seq = self.seq.wrapping_add(1);
+        let request = build_request(self.seq, family, protocol, states, with_info);
+        self.send(&request)?;
+
+        loop {
+            let received = self.recv()?;
+            if process_batch(&self.buf[..received], self.seq, on_message)? {
+                return Ok(());
+            }
+        }
     }
-    let _guard = FdGuard(fd);
 
-    let mut addr: sockaddr_nl = unsafe { zeroed() };
-    addr.nl_family = libc::AF_NETLINK as u16;
-    addr.nl_pid = 0;
-    addr.nl_groups = 0;
+    fn send(&self, request: &[u8]) -> io::Result<()> {
+        // SAFETY: sockaddr_nl 为纯数据结构，全 0 即为合法值
+        let mut addr: sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
 
-    // sendto
+        let sent = unsafe {
+            libc::sendto(
+                self.fd.as_raw_fd(),
+                request.as_ptr().cast::<c_void>(),
+                request.len(),
+                0,
+                (&raw const addr).cast::<sockaddr>(),
+                socklen_of::<sockaddr_nl>(),
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// 返回本次收到的字节数，被信号中断时重试，其余错误 (包括超时) 原样返回
+    fn recv(&mut self) -> io::Result<usize> {
+        loop {
+            let received = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    self.buf.as_mut_ptr().cast::<c_void>(),
+                    self.buf.len(),
+                    0,
+                )
+            };
+            if received == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if let Ok(received) = usize::try_from(received) {
+                return Ok(received);
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+fn set_socket_option<T>(fd: &OwnedFd, option: libc::c_int, value: &T) -> io::Result<()> {
     let ret = unsafe {
-        sendto(
-            fd,
-            request.as_ptr() as *const c_void,
-            request.len(),
-            0,
-            &addr as *const sockaddr_nl as *const sockaddr,
-            size_of::<sockaddr_nl>() as u32,
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            std::ptr::from_ref(value).cast::<c_void>(),
+            socklen_of::<T>(),
         )
     };
     if ret < 0 {
         return Err(io::Error::last_os_error());
     }
+    Ok(())
+}
 
-    // 准备读 buffer
-    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
-    let mut buf: Vec<u8> = vec![0u8; page_size];
+fn socklen_of<T>() -> socklen_t {
+    socklen_t::try_from(size_of::<T>()).unwrap_or(socklen_t::MAX)
+}
 
-    let mut total_count: u64 = 0;
+/// 序列化 `nlmsghdr` + `inet_diag_req_v2`，均为本机字节序
+fn build_request(seq: u32, family: u8, protocol: u8, states: u32, with_info: bool) -> Vec<u8> {
+    let total = NLMSG_HDRLEN + INET_DIAG_REQ_LEN;
+    let mut msg = Vec::with_capacity(total);
+
+    msg.extend_from_slice(&u32::try_from(total).unwrap_or(u32::MAX).to_ne_bytes());
+    msg.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    msg.extend_from_slice(&((libc::NLM_F_DUMP | libc::NLM_F_REQUEST) as u16).to_ne_bytes());
+    msg.extend_from_slice(&seq.to_ne_bytes());
+    // 端口号为 0 时由内核分配
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+
+    let ext = if with_info {
+        1 << (INET_DIAG_INFO - 1)
+    } else {
+        0
+    };
+    msg.extend_from_slice(&[family, protocol, ext, 0]);
+    msg.extend_from_slice(&states.to_ne_bytes());
+    // inet_diag_sockid 全部为 0，不按地址或端口过滤
+    msg.resize(total, 0);
 
-    loop {
-        // 每次用整块 buf，当次有效长度是 nr
-        let nr = unsafe {
-            recvfrom(
-                fd,
-                buf.as_mut_ptr() as *mut c_void,
-                buf.len(),
-                0,
-                ptr::null_mut(),
-                ptr::null_mut(),
-            )
-        };
-        if nr < 0 {
-            return Err(io::Error::last_os_error());
+    msg
+}
+
+/// 对齐到 4 字节
+fn nlm_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn read_u16(b: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_ne_bytes(
+        b.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(b: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_i32(b: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_ne_bytes(
+        b.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(b: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_ne_bytes(
+        b.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn parse_header(b: &[u8]) -> Option<NetlinkHeader> {
+    Some(NetlinkHeader {
+        len: usize::try_from(read_u32(b, 0)?).ok()?,
+        msg_type: read_u16(b, 4)?,
+        flags: read_u16(b, 6)?,
+        seq: read_u32(b, 8)?,
+    })
+}
+
+/// 解析批次开头的一条消息，返回其头部、内容与剩余部分；长度越界时返回 EINVAL
+fn parse_message(b: &[u8]) -> io::Result<(NetlinkHeader, NetlinkMessage<'_>, &[u8])> {
+    let invalid = || io::Error::from_raw_os_error(libc::EINVAL);
+    let header = parse_header(b).ok_or_else(invalid)?;
+    if header.len < NLMSG_HDRLEN || header.len > b.len() {
+        return Err(invalid());
+    }
+    let payload = &b[NLMSG_HDRLEN..header.len];
+    // 最后一条消息可能没有对齐填充
+    let rest = b.get(nlm_align(header.len)..).unwrap_or_default();
+
+    let message = match header.msg_type {
+        SOCK_DIAG_BY_FAMILY => NetlinkMessage::SockDiag(payload),
+        t if t == libc::NLMSG_DONE as u16 => {
+            // DONE 的内容为 int，非 0 表示转储中途出错
+            match read_i32(payload, 0) {
+                Some(errno) if errno < 0 => NetlinkMessage::Error(errno),
+                _ => NetlinkMessage::Done,
+            }
         }
-        let nr = nr as usize;
-        if nr < NLMSG_HDRLEN {
-            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        t if t == libc::NLMSG_ERROR as u16 => {
+            NetlinkMessage::Error(read_i32(payload, 0).ok_or_else(invalid)?)
         }
+        _ => NetlinkMessage::Other,
+    };
 
-        let slice = &buf[..nr];
+    Ok((header, message, rest))
+}
 
-        let (count, done) = count_netlink_messages(slice)?;
-        total_count += count;
-        if done {
+/// 处理一次 recv 收到的消息，对序号为 `seq` 的 `sock_diag` 回复调用 `on_message`，回复结束时返回 true
+///
+/// 序号不符的消息 (上次中断的查询残留) 直接丢弃
+fn process_batch(
+    mut batch: &[u8],
+    seq: u32,
+    on_message: &mut dyn FnMut(&[u8]),
+) -> io::Result<bool> {
+    while !batch.is_empty() {
+        let (header, message, rest) = parse_message(batch)?;
+        batch = rest;
+        if header.seq != seq {
+            continue;
+        }
+        match message {
+            NetlinkMessage::SockDiag(payload) => on_message(payload),
+            NetlinkMessage::Done | NetlinkMessage::Error(0) => return Ok(true),
+            NetlinkMessage::Error(errno) => {
+                return Err(io::Error::from_raw_os_error(-errno));
+            }
+            NetlinkMessage::Other => {}
+        }
+        // 不属于多段回复的消息之后不会再有后续
+        if header.flags & libc::NLM_F_MULTI as u16 == 0 {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 解析 `inet_diag_msg` 及其后的 `INET_DIAG_INFO` 属性
+///
+/// 端口与地址为网络字节序，其余字段为本机字节序
+fn parse_inet_diag_msg(payload: &[u8]) -> Option<InetSocket> {
+    if payload.len() < INET_DIAG_MSG_LEN {
+        return None;
+    }
+    let family = i32::from(payload[0]);
+    let state = payload[1];
+
+    let address = |offset: usize| -> Option<IpAddr> {
+        let raw = payload.get(offset..offset + 16)?;
+        if family == libc::AF_INET {
+            Some(IpAddr::V4(Ipv4Addr::new(raw[0], raw[1], raw[2], raw[3])))
+        } else {
+            let octets: [u8; 16] = raw.try_into().ok()?;
+            Some(Ipv6Addr::from(octets).to_canonical())
+        }
+    };
+    let local_port = u16::from_be_bytes([payload[4], payload[5]]);
+    let remote_port = u16::from_be_bytes([payload[6], payload[7]]);
+    let local = SocketAddr::new(address(8)?, local_port);
+    let remote = SocketAddr::new(address(24)?, remote_port);
+    let cookie = u64::from(read_u32(payload, 44)?) | (u64::from(read_u32(payload, 48)?) << 32);
+    let inode = read_u32(payload, 68)?;
+
+    // 其后为 rtattr 列表: u16 长度 (含 4 字节头部) + u16 类型，按 4 字节对齐
+    let mut bytes = None;
+    let mut attributes = &payload[INET_DIAG_MSG_LEN..];
+    while let (Some(len), Some(attr_type)) = (read_u16(attributes, 0), read_u16(attributes, 2)) {
+        let len = usize::from(len);
+        if len < 4 || len > attributes.len() {
             break;
         }
+        if attr_type == INET_DIAG_INFO {
+            let info = &attributes[4..len];
+            if let (Some(acked), Some(received)) = (
+                read_u64(info, TCPI_BYTES_ACKED_OFFSET),
+                read_u64(info, TCPI_BYTES_RECEIVED_OFFSET),
+            ) {
+                bytes = Some((acked, received));
+            }
+        }
+        attributes = attributes.get(nlm_align(len)..).unwrap_or_default();
     }
 
-    Ok(total_count)
+    Some(InetSocket {
+        state,
+        local,
+        remote,
+        inode,
+        cookie,
+        bytes,
+    })
 }
 
-/// 只数本批次 buffer 里的 netlink 消息条数；遇到 DONE/ERROR 返回 done=true
-fn count_netlink_messages(mut b: &[u8]) -> io::Result<(u64, bool)> {
-    let mut msgs: u64 = 0;
-    let mut done = false;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTI: u16 = libc::NLM_F_MULTI as u16;
+    const DONE: u16 = libc::NLMSG_DONE as u16;
+    const ERROR: u16 = libc::NLMSG_ERROR as u16;
+
+    /// 按内核格式拼出一条 netlink 消息，末尾补齐到 4 字节
+    fn message(msg_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(NLMSG_HDRLEN + payload.len()).unwrap();
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&len.to_ne_bytes());
+        msg.extend_from_slice(&msg_type.to_ne_bytes());
+        msg.extend_from_slice(&flags.to_ne_bytes());
+        msg.extend_from_slice(&seq.to_ne_bytes());
+        msg.extend_from_slice(&0u32.to_ne_bytes());
+        msg.extend_from_slice(payload);
+        msg.resize(nlm_align(msg.len()), 0);
+        msg
+    }
 
-    while b.len() >= NLMSG_HDRLEN {
-        let (dlen, at_end) = netlink_message_header(b)?;
-        msgs += 1;
-        if at_end {
-            done = true;
-            break;
+    fn inet_diag_msg(state: u8, local: SocketAddr, remote: SocketAddr, inode: u32) -> Vec<u8> {
+        let mut payload = vec![0; INET_DIAG_MSG_LEN];
+        payload[0] = if local.is_ipv4() {
+            libc::AF_INET as u8
+        } else {
+            libc::AF_INET6 as u8
+        };
+        payload[1] = state;
+        payload[4..6].copy_from_slice(&local.port().to_be_bytes());
+        payload[6..8].copy_from_slice(&remote.port().to_be_bytes());
+        for (offset, address) in [(8, local.ip()), (24, remote.ip())] {
+            match address {
+                IpAddr::V4(v4) => payload[offset..offset + 4].copy_from_slice(&v4.octets()),
+                IpAddr::V6(v6) => payload[offset..offset + 16].copy_from_slice(&v6.octets()),
+            }
         }
-        b = &b[dlen..];
+        payload[44..48].copy_from_slice(&7u32.to_ne_bytes());
+        payload[48..52].copy_from_slice(&1u32.to_ne_bytes());
+        payload[68..72].copy_from_slice(&inode.to_ne_bytes());
+        payload
     }
 
-    Ok((msgs, done))
-}
+    /// 追加长度为 `info_len` 的 `INET_DIAG_INFO` 属性，字节数写在 `tcp_info` 中对应的偏移
+    fn with_tcp_info(mut payload: Vec<u8>, info_len: usize, acked: u64, received: u64) -> Vec<u8> {
+        let mut info = vec![0; info_len];
+        if info_len >= TCPI_BYTES_RECEIVED_OFFSET + 8 {
+            info[TCPI_BYTES_ACKED_OFFSET..TCPI_BYTES_ACKED_OFFSET + 8]
+                .copy_from_slice(&acked.to_ne_bytes());
+            info[TCPI_BYTES_RECEIVED_OFFSET..TCPI_BYTES_RECEIVED_OFFSET + 8]
+                .copy_from_slice(&received.to_ne_bytes());
+        }
+        payload.extend_from_slice(&u16::try_from(info_len + 4).unwrap().to_ne_bytes());
+        payload.extend_from_slice(&INET_DIAG_INFO.to_ne_bytes());
+        payload.extend_from_slice(&info);
+        payload.resize(nlm_align(payload.len()), 0);
+        payload
+    }
 
-/// 解析当前切片的 nlmsghdr，返回（对齐后的本条长度，是否 DONE/ERROR）
-fn netlink_message_header(b: &[u8]) -> io::Result<(usize, bool)> {
-    if b.len() < NLMSG_HDRLEN {
-        return Err(io::Error::from_raw_os_error(libc::EINVAL));
+    fn socket(inode: u32) -> Vec<u8> {
+        inet_diag_msg(
+            1,
+            "10.0.0.1:22".parse().unwrap(),
+            "10.0.0.2:50000".parse().unwrap(),
+            inode,
+        )
     }
 
-    // 安全地读出头（按本机字节序）
-    let h = unsafe { &*(b.as_ptr() as *const libc::nlmsghdr) };
-    let len = h.nlmsg_len as usize;
-    let l = nlm_align_of(len as i32) as usize;
+    /// 返回 `process_batch` 的结果与收到的 `sock_diag` 回复数
+    fn process(batch: &[u8], seq: u32) -> (io::Result<bool>, usize) {
+        let mut received = 0;
+        let result = process_batch(batch, seq, &mut |_| received += 1);
+        (result, received)
+    }
 
-    if len < NLMSG_HDRLEN || l > b.len() {
-        return Err(io::Error::from_raw_os_error(libc::EINVAL));
+    #[test]
+    fn multipart_dump_ends_with_done() {
+        let first = [
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 3, &socket(1)),
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 3, &socket(2)),
+        ]
+        .concat();
+        let (result, received) = process(&first, 3);
+        assert!(!result.unwrap());
+        assert_eq!(received, 2);
+
+        let second = [
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 3, &socket(3)),
+            message(DONE, MULTI, 3, &0i32.to_ne_bytes()),
+        ]
+        .concat();
+        let (result, received) = process(&second, 3);
+        assert!(result.unwrap());
+        assert_eq!(received, 1);
     }
 
-    if h.nlmsg_type == libc::NLMSG_DONE as u16 || h.nlmsg_type == libc::NLMSG_ERROR as u16 {
-        return Ok((l, true));
+    #[test]
+    fn done_with_negative_errno_is_an_error() {
+        let batch = [
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 1, &socket(1)),
+            message(DONE, MULTI, 1, &(-libc::ENOBUFS).to_ne_bytes()),
+        ]
+        .concat();
+        let (result, received) = process(&batch, 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::ENOBUFS));
+        assert_eq!(received, 1);
     }
 
-    Ok((l, false))
-}
+    #[test]
+    fn done_without_payload_ends_dump() {
+        let (result, _) = process(&message(DONE, MULTI, 1, &[]), 1);
+        assert!(result.unwrap());
+    }
 
-/// 对齐到 4 字节
-#[inline]
-fn nlm_align_of(msglen: i32) -> i32 {
-    (msglen + libc::NLA_ALIGNTO - 1) & !(libc::NLA_ALIGNTO - 1)
-}
-
-/// 将 (header, payload) 序列化为一条 Netlink 消息（回填 header.len）
-fn serialize_netlink_message(hdr: &libc::nlmsghdr, req: &InetDiagReqV2) -> io::Result<Vec<u8>> {
-    let total = NLMSG_HDRLEN + size_of::<InetDiagReqV2>();
-    let mut msg = vec![0u8; total];
-
-    // 写 header（先拷一份，回填 nlmsg_len）
-    let mut h = *hdr;
-    h.nlmsg_len = total as u32;
-
-    unsafe {
-        // header
-        ptr::copy_nonoverlapping(
-            &h as *const libc::nlmsghdr as *const u8,
-            msg.as_mut_ptr(),
-            NLMSG_HDRLEN,
-        );
-        // payload
-        ptr::copy_nonoverlapping(
-            req as *const InetDiagReqV2 as *const u8,
-            msg.as_mut_ptr().add(NLMSG_HDRLEN),
-            size_of::<InetDiagReqV2>(),
+    #[test]
+    fn nlmsg_error_reports_errno_and_ack_ends_query() {
+        // NLMSG_ERROR 的内容为负的 errno 加上出错的请求头部
+        let error = [(-libc::EPERM).to_ne_bytes().as_slice(), &[0; NLMSG_HDRLEN]].concat();
+        let (result, _) = process(&message(ERROR, 0, 1, &error), 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EPERM));
+
+        let ack = [0i32.to_ne_bytes().as_slice(), &[0; NLMSG_HDRLEN]].concat();
+        let (result, _) = process(&message(ERROR, 0, 1, &ack), 1);
+        assert!(result.unwrap());
+
+        let (result, _) = process(&message(ERROR, 0, 1, &[0; 2]), 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EINVAL));
+    }
+
+    #[test]
+    fn invalid_nlmsg_len_is_rejected() {
+        let mut too_short = message(SOCK_DIAG_BY_FAMILY, MULTI, 1, &socket(1));
+        too_short[..4].copy_from_slice(&(u32::try_from(NLMSG_HDRLEN).unwrap() - 1).to_ne_bytes());
+        let (result, received) = process(&too_short, 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EINVAL));
+        assert_eq!(received, 0);
+
+        let mut too_long = message(SOCK_DIAG_BY_FAMILY, MULTI, 1, &socket(1));
+        let len = u32::try_from(too_long.len() + 4).unwrap();
+        too_long[..4].copy_from_slice(&len.to_ne_bytes());
+        let (result, received) = process(&too_long, 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EINVAL));
+        assert_eq!(received, 0);
+
+        let (result, _) = process(&[0; NLMSG_HDRLEN - 1], 1);
+        assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EINVAL));
+    }
+
+    #[test]
+    fn last_message_without_padding_is_accepted() {
+        let mut batch = message(SOCK_DIAG_BY_FAMILY, MULTI, 1, &[1, 2, 3, 4, 5]);
+        batch.truncate(NLMSG_HDRLEN + 5);
+        let (header, message, rest) = parse_message(&batch).unwrap();
+        assert_eq!(header.len, NLMSG_HDRLEN + 5);
+        assert_eq!(message, NetlinkMessage::SockDiag(&[1, 2, 3, 4, 5]));
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn mismatched_sequence_numbers_are_skipped() {
+        let batch = [
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 4, &socket(1)),
+            message(DONE, MULTI, 4, &0i32.to_ne_bytes()),
+            message(SOCK_DIAG_BY_FAMILY, MULTI, 5, &socket(2)),
+        ]
+        .concat();
+        let (result, received) = process(&batch, 5);
+        assert!(!result.unwrap());
+        assert_eq!(received, 1);
+
+        let (result, _) = process(&message(DONE, MULTI, 5, &0i32.to_ne_bytes()), 5);
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn non_multipart_reply_ends_query() {
+        let (result, received) = process(&message(SOCK_DIAG_BY_FAMILY, 0, 1, &socket(1)), 1);
+        assert!(result.unwrap());
+        assert_eq!(received, 1);
+    }
+
+    #[test]
+    fn parses_inet_diag_msg_with_tcp_info() {
+        let payload = with_tcp_info(socket(42), TCPI_BYTES_RECEIVED_OFFSET + 8, 1000, 2000);
+        let socket = parse_inet_diag_msg(&payload).unwrap();
+        assert_eq!(socket.state, 1);
+        assert_eq!(socket.local, "10.0.0.1:22".parse().unwrap());
+        assert_eq!(socket.remote, "10.0.0.2:50000".parse().unwrap());
+        assert_eq!(socket.inode, 42);
+        assert_eq!(socket.cookie, (1 << 32) | 7);
+        assert_eq!(socket.bytes, Some((1000, 2000)));
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6_addresses_are_canonicalized() {
+        let payload = inet_diag_msg(
+            10,
+            "[::ffff:192.0.2.1]:443".parse().unwrap(),
+            "[2001:db8::1]:0".parse().unwrap(),
+            1,
         );
+        let socket = parse_inet_diag_msg(&payload).unwrap();
+        assert_eq!(socket.local, "192.0.2.1:443".parse().unwrap());
+        assert_eq!(socket.remote, "[2001:db8::1]:0".parse().unwrap());
     }
 
-    Ok(msg)
-}
+    #[test]
+    fn truncated_inet_diag_msg_is_skipped() {
+        let payload = socket(1);
+        assert!(parse_inet_diag_msg(&payload[..INET_DIAG_MSG_LEN - 1]).is_none());
+        assert!(parse_inet_diag_msg(&[]).is_none());
+    }
+
+    #[test]
+    fn truncated_tcp_info_has_no_byte_counts() {
+        // 旧内核的 tcp_info 不含累计字节数
+        let payload = with_tcp_info(socket(1), TCPI_BYTES_ACKED_OFFSET, 0, 0);
+        assert_eq!(parse_inet_diag_msg(&payload).unwrap().bytes, None);
+
+        // 属性长度超出剩余内容时停止解析
+        let mut payload = with_tcp_info(socket(1), TCPI_BYTES_RECEIVED_OFFSET + 8, 1, 2);
+        payload.truncate(payload.len() - 8);
+        assert_eq!(parse_inet_diag_msg(&payload).unwrap().bytes, None);
+
+        // 长度小于属性头部时停止解析
+        let mut payload = socket(1);
+        payload.extend_from_slice(&2u16.to_ne_bytes());
+        payload.extend_from_slice(&INET_DIAG_INFO.to_ne_bytes());
+        assert_eq!(parse_inet_diag_msg(&payload).unwrap().bytes, None);
+    }
 
-/// 简易 FD 守卫
-struct FdGuard(RawFd);
-impl Drop for FdGuard {
-    fn drop(&mut self) {
-        unsafe { close(self.0) };
+    #[test]
+    fn request_layout_matches_inet_diag_req_v2() {
+        let request = build_request(
+            9,
+            libc::AF_INET6 as u8,
+            libc::IPPROTO_TCP as u8,
+            1 << 10,
+            true,
+        );
+        assert_eq!(request.len(), NLMSG_HDRLEN + INET_DIAG_REQ_LEN);
+        let header = parse_header(&request).unwrap();
+        assert_eq!(header.len, request.len());
+        assert_eq!(header.msg_type, SOCK_DIAG_BY_FAMILY);
+        assert_eq!(header.seq, 9);
+        assert_eq!(
+            &request[NLMSG_HDRLEN..NLMSG_HDRLEN + 4],
+            [
+                libc::AF_INET6 as u8,
+                libc::IPPROTO_TCP as u8,
+                1 << (INET_DIAG_INFO - 1),
+                0
+            ]
+        );
+        assert_eq!(read_u32(&request, NLMSG_HDRLEN + 4), Some(1 << 10));
     }
 }
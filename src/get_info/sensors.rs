@@ -0,0 +1,284 @@
+use crate::data_struct::{SensorReading, Sensors};
+use crate::get_info::filter::NameFilter;
+use log::trace;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 可能代表 CPU 封装温度的传感器名称
+const CPU_PACKAGE_SENSORS: [&str; 7] = [
+    "coretemp/Package id *",
+    "k10temp/Tctl",
+    "k10temp/Tdie",
+    "zenpower/Tdie",
+    "x86_pkg_temp/*",
+    "cpu_thermal/*",
+    "cpu-thermal/*",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SensorKind {
+    Temperature,
+    Fan,
+    Voltage,
+}
+
+impl SensorKind {
+    /// sysfs 中的原始值到上报单位 (°C、RPM、V) 的换算系数
+    fn scale(self) -> f64 {
+        match self {
+            SensorKind::Temperature | SensorKind::Voltage => 1000.0,
+            SensorKind::Fan => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SensorSource {
+    name: String,
+    kind: SensorKind,
+    path: PathBuf,
+}
+
+/// 启动时枚举 `/sys/class/hwmon` 与 `/sys/class/thermal` 中的传感器，每个周期只读取数值
+///
+/// 传感器名称为 `芯片名/标签`，热区为 `类型/thermal_zoneN`，包含与排除列表按该名称匹配
+#[derive(Debug)]
+pub struct SensorCollector {
+    sources: Vec<SensorSource>,
+    package_filter: NameFilter,
+}
+
+impl SensorCollector {
+    pub fn new(filter: &NameFilter) -> Self {
+        Self::with_root(Path::new("/sys/class"), filter)
+    }
+
+    /// `root` 对应 `/sys/class`，便于在其他目录中构造模拟的 sysfs
+    pub fn with_root(root: &Path, filter: &NameFilter) -> Self {
+        let mut sources = discover_hwmon(&root.join("hwmon"));
+        let hwmon_chips: Vec<String> = sources
+            .iter()
+            .filter_map(|source| source.name.split('/').next().map(str::to_string))
+            .collect();
+        sources.extend(discover_thermal(&root.join("thermal"), &hwmon_chips));
+        sources.retain(|source| filter.allows(&source.name));
+
+        trace!("发现 {} 个传感器: {sources:?}", sources.len());
+
+        Self {
+            sources,
+            package_filter: NameFilter::new(&CPU_PACKAGE_SENSORS.map(str::to_string), &[]),
+        }
+    }
+
+    pub fn collect(&self) -> Sensors {
+        let mut sensors = Sensors {
+            temperatures: Vec::new(),
+            fans: Vec::new(),
+            voltages: Vec::new(),
+            cpu_package_temperature: None,
+        };
+
+        for source in &self.sources {
+            // 读取失败的传感器 (设备休眠、驱动报错等) 本周期跳过
+            let Some(raw) = read_number(&source.path) else {
+                continue;
+            };
+            let reading = SensorReading {
+                name: source.name.clone(),
+                value: raw / source.kind.scale(),
+            };
+
+            match source.kind {
+                SensorKind::Temperature => {
+                    if self.package_filter.allows(&reading.name) {
+                        sensors.cpu_package_temperature = Some(
+                            sensors
+                                .cpu_package_temperature
+                                .map_or(reading.value, |max: f64| max.max(reading.value)),
+                        );
+                    }
+                    sensors.temperatures.push(reading);
+                }
+                SensorKind::Fan => sensors.fans.push(reading),
+                SensorKind::Voltage => sensors.voltages.push(reading),
+            }
+        }
+
+        trace!("REALTIME SENSORS 获取成功: {sensors:?}");
+        sensors
+    }
+}
+
+fn discover_hwmon(hwmon_root: &Path) -> Vec<SensorSource> {
+    let mut sources = Vec::new();
+
+    for chip_dir in sorted_entries(hwmon_root) {
+        let Some(chip) = read_trimmed(&chip_dir.join("name")) else {
+            continue;
+        };
+
+        for path in sorted_entries(&chip_dir) {
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(channel) = file_name.strip_suffix("_input") else {
+                continue;
+            };
+            let kind = if channel.starts_with("temp") {
+                SensorKind::Temperature
+            } else if channel.starts_with("fan") {
+                SensorKind::Fan
+            } else if channel
+                .strip_prefix("in")
+                .is_some_and(|index| index.starts_with(|c: char| c.is_ascii_digit()))
+            {
+                SensorKind::Voltage
+            } else {
+                continue;
+            };
+
+            let label = read_trimmed(&chip_dir.join(format!("{channel}_label")))
+                .unwrap_or_else(|| channel.to_string());
+            sources.push(SensorSource {
+                name: format!("{chip}/{label}"),
+                kind,
+                path,
+            });
+        }
+    }
+
+    sources
+}
+
+/// 热区通常也会注册为同名的 hwmon 芯片，这类热区不再重复上报
+fn discover_thermal(thermal_root: &Path, hwmon_chips: &[String]) -> Vec<SensorSource> {
+    sorted_entries(thermal_root)
+        .into_iter()
+        .filter_map(|zone_dir| {
+            let zone = zone_dir.file_name()?.to_str()?.to_string();
+            if !zone.starts_with("thermal_zone") {
+                return None;
+            }
+            let zone_type = read_trimmed(&zone_dir.join("type"))?;
+            if hwmon_chips.contains(&zone_type) {
+                return None;
+            }
+            Some(SensorSource {
+                name: format!("{zone_type}/{zone}"),
+                kind: SensorKind::Temperature,
+                path: zone_dir.join("temp"),
+            })
+        })
+        .collect()
+}
+
+/// 按名称排序，保证每次上报的顺序一致
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    paths
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let content = content.trim();
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    read_trimmed(path)?
+        .parse::<i64>()
+        .ok()
+        .map(|value| value as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn fake_sysfs() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(root, "hwmon/hwmon0/name", "coretemp\n");
+        write(root, "hwmon/hwmon0/temp1_input", "52000\n");
+        write(root, "hwmon/hwmon0/temp1_label", "Package id 0\n");
+        write(root, "hwmon/hwmon0/temp2_input", "48500\n");
+        write(root, "hwmon/hwmon0/temp2_label", "Core 0\n");
+        write(root, "hwmon/hwmon1/name", "nct6775\n");
+        write(root, "hwmon/hwmon1/fan1_input", "1200\n");
+        write(root, "hwmon/hwmon1/in0_input", "1104\n");
+        write(root, "hwmon/hwmon1/intrusion0_alarm", "0\n");
+        // 无标签时使用通道名，读取失败的传感器跳过
+        write(root, "hwmon/hwmon1/temp3_input", "-5000\n");
+        write(root, "hwmon/hwmon1/temp4_input", "\n");
+        write(root, "hwmon/hwmon2/temp1_input", "40000\n");
+        write(root, "thermal/thermal_zone0/type", "acpitz\n");
+        write(root, "thermal/thermal_zone0/temp", "27800\n");
+        // 已作为 hwmon 芯片上报的热区不重复上报
+        write(root, "thermal/thermal_zone1/type", "coretemp\n");
+        write(root, "thermal/thermal_zone1/temp", "52000\n");
+        write(root, "thermal/cooling_device0/type", "Processor\n");
+        dir
+    }
+
+    fn names(readings: &[SensorReading]) -> Vec<(&str, f64)> {
+        readings
+            .iter()
+            .map(|reading| (reading.name.as_str(), reading.value))
+            .collect()
+    }
+
+    #[test]
+    fn reads_hwmon_and_thermal_sensors() {
+        let dir = fake_sysfs();
+        let sensors = SensorCollector::with_root(dir.path(), &NameFilter::default()).collect();
+
+        assert_eq!(
+            names(&sensors.temperatures),
+            [
+                ("coretemp/Package id 0", 52.0),
+                ("coretemp/Core 0", 48.5),
+                ("nct6775/temp3", -5.0),
+                ("acpitz/thermal_zone0", 27.8),
+            ]
+        );
+        assert_eq!(names(&sensors.fans), [("nct6775/fan1", 1200.0)]);
+        assert_eq!(names(&sensors.voltages), [("nct6775/in0", 1.104)]);
+        assert_eq!(sensors.cpu_package_temperature, Some(52.0));
+    }
+
+    #[test]
+    fn applies_include_and_exclude_filters() {
+        let dir = fake_sysfs();
+        let filter = NameFilter::new(
+            &["coretemp/*".to_string(), "nct6775/fan?".to_string()],
+            &["*/Core *".to_string()],
+        );
+        let sensors = SensorCollector::with_root(dir.path(), &filter).collect();
+
+        assert_eq!(
+            names(&sensors.temperatures),
+            [("coretemp/Package id 0", 52.0)]
+        );
+        assert_eq!(names(&sensors.fans), [("nct6775/fan1", 1200.0)]);
+        assert!(sensors.voltages.is_empty());
+    }
+
+    #[test]
+    fn missing_sysfs_yields_no_sensors() {
+        let dir = tempfile::tempdir().unwrap();
+        let sensors = SensorCollector::with_root(dir.path(), &NameFilter::default()).collect();
+        assert!(sensors.temperatures.is_empty());
+        assert_eq!(sensors.cpu_package_temperature, None);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::command_parser::Args;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 读取任务最近一次收到任意帧的时间及是否已退出，由读取任务更新，上报循环检查
+#[derive(Debug)]
+pub struct Liveness {
+    epoch: Instant,
+    last_seen_ns: AtomicU64,
+    reader_closed: AtomicBool,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_seen_ns: AtomicU64::new(0),
+            reader_closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn touch(&self) {
+        let elapsed = u64::try_from(self.epoch.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.last_seen_ns.store(elapsed, Ordering::Relaxed);
+    }
+
+    pub fn close_reader(&self) {
+        self.reader_closed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_reader_closed(&self) -> bool {
+        self.reader_closed.load(Ordering::Relaxed)
+    }
+
+    fn last_seen(&self) -> Instant {
+        self.epoch + Duration::from_nanos(self.last_seen_ns.load(Ordering::Relaxed))
+    }
+}
+
+/// 上报连接的应用层存活检测
+///
+/// 每隔 `ping_interval` 发送一次 Ping，发送后 `pong_timeout` 内读取任务未收到任何帧
+/// (Pong 或主端消息) 即认为连接已失效，避免半开 TCP 连接长时间无法被发现
+#[derive(Debug)]
+pub struct LivenessMonitor {
+    liveness: Arc<Liveness>,
+    ping_interval: Duration,
+    pong_timeout: Duration,
+    last_ping: Instant,
+    pending_ping: Option<Instant>,
+}
+
+impl LivenessMonitor {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            liveness: Arc::new(Liveness::new()),
+            ping_interval: Duration::from_secs(args.ping_interval),
+            pong_timeout: Duration::from_secs(args.pong_timeout.max(1)),
+            last_ping: Instant::now(),
+            pending_ping: None,
+        }
+    }
+
+    pub fn liveness(&self) -> Arc<Liveness> {
+        self.liveness.clone()
+    }
+
+    /// 单次写入的超时时间，半开连接的发送缓冲区写满后 send 会一直阻塞
+    pub fn send_timeout(&self) -> Duration {
+        self.pong_timeout
+    }
+
+    /// 检查 Pong 是否超时，返回现在是否需要发送 Ping
+    pub fn poll(&mut self) -> Result<bool, String> {
+        if self.ping_interval.is_zero() {
+            return Ok(false);
+        }
+
+        if let Some(sent) = self.pending_ping {
+            if self.liveness.last_seen() >= sent {
+                self.pending_ping = None;
+            } else if sent.elapsed() > self.pong_timeout {
+                return Err(format!(
+                    "发送 Ping 后 {} 秒内未收到任何响应",
+                    self.pong_timeout.as_secs()
+                ));
+            } else {
+                return Ok(false);
+            }
+        }
+
+        if self.last_ping.elapsed() < self.ping_interval {
+            return Ok(false);
+        }
+
+        let now = Instant::now();
+        self.last_ping = now;
+        self.pending_ping = Some(now);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use tokio::time::advance;
+
+    fn monitor(ping_interval: &str, pong_timeout: &str) -> LivenessMonitor {
+        let args = Args::try_parse_from([
+            "komari-monitor-rs",
+            "--http-server",
+            "http://panel",
+            "-t",
+            "t",
+            "--ping-interval",
+            ping_interval,
+            "--pong-timeout",
+            pong_timeout,
+        ])
+        .unwrap();
+        LivenessMonitor::new(&args)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pings_once_per_interval_while_frames_arrive() {
+        let mut monitor = monitor("30", "10");
+        let liveness = monitor.liveness();
+        assert_eq!(monitor.poll(), Ok(false));
+
+        advance(Duration::from_secs(30)).await;
+        assert_eq!(monitor.poll(), Ok(true));
+        assert_eq!(monitor.poll(), Ok(false), "等待 Pong 期间不重复发送");
+
+        advance(Duration::from_secs(5)).await;
+        liveness.touch();
+        assert_eq!(monitor.poll(), Ok(false));
+
+        advance(Duration::from_secs(25)).await;
+        assert_eq!(monitor.poll(), Ok(true));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missing_pong_times_out() {
+        let mut monitor = monitor("30", "10");
+        advance(Duration::from_secs(30)).await;
+        assert_eq!(monitor.poll(), Ok(true));
+
+        advance(Duration::from_secs(10)).await;
+        assert_eq!(monitor.poll(), Ok(false), "恰好到达超时时间时仍在等待");
+        advance(Duration::from_millis(1)).await;
+        assert!(monitor.poll().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frames_before_the_ping_do_not_count_as_pong() {
+        let mut monitor = monitor("30", "10");
+        let liveness = monitor.liveness();
+        advance(Duration::from_secs(29)).await;
+        liveness.touch();
+
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(monitor.poll(), Ok(true));
+        advance(Duration::from_secs(11)).await;
+        assert!(monitor.poll().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_interval_disables_ping() {
+        let mut monitor = monitor("0", "10");
+        advance(Duration::from_hours(1)).await;
+        assert_eq!(monitor.poll(), Ok(false));
+        assert_eq!(monitor.send_timeout(), Duration::from_secs(10));
+    }
+}
@@ -2,42 +2,59 @@
 #![allow(
     clippy::cast_sign_loss,
     clippy::cast_precision_loss,
-    clippy::cast_precision_loss,
     clippy::cast_possible_truncation,
     clippy::similar_names,
     clippy::too_many_lines
 )]
 
-use crate::callbacks::handle_callbacks;
-use crate::command_parser::Args;
-use crate::data_struct::{BasicInfo, RealTimeInfo};
-use crate::utils::{build_urls, connect_ws, init_logger};
-use futures::stream::SplitSink;
-use futures::{SinkExt, StreamExt};
-use log::{error, info};
+use crate::command_parser::{Args, Command};
+use crate::data_struct::BasicInfo;
+use crate::get_info::Collector;
+use crate::target::{build_targets, run_target, target_fake};
+use crate::utils::init_logger;
+use log::{error, info, warn};
 use miniserde::json;
+use std::fmt;
 use std::sync::Arc;
 use std::time::Duration;
-use sysinfo::{CpuRefreshKind, DiskRefreshKind, Disks, MemoryRefreshKind, Networks, RefreshKind};
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use tokio::time::sleep;
-use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinSet;
+use tokio::time::{Instant, sleep};
 
+mod auth;
 mod callbacks;
 mod command_parser;
+mod config_file;
 mod data_struct;
+mod failover;
 mod get_info;
+mod liveness;
+mod offline_buffer;
+mod proxy;
+mod reconnect;
 mod rustls_config;
+mod supervisor;
+mod target;
+mod traffic_history;
+mod traffic_ledger;
+mod traffic_store;
 mod utils;
 
 #[tokio::main]
 async fn main() {
-    let args = Args::par();
+    let mut args = Args::par();
 
     init_logger(&args.log_level);
 
+    if let Some(Command::Traffic(query)) = &args.command {
+        if let Err(e) = traffic_history::run_query(&args, query) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     #[cfg(all(feature = "nyquest-support", not(target_os = "linux")))]
     {
         nyquest_preset::register();
@@ -50,86 +67,195 @@ async fn main() {
         info!("komari-monitor-rs 正在使用 Nyquest 作为 Http Client，该功能暂未稳定，请谨慎使用");
     }
 
-    let connection_urls =
-        build_urls(&args.http_server, args.ws_server.as_ref(), &args.token).unwrap();
+    if let Err(e) = proxy::init(args.proxy.as_ref().map(proxy::ProxyUrl::as_str)) {
+        error!("{e}");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = rustls_config::init(&args) {
+        error!("{e}");
+        std::process::exit(1);
+    }
+
+    get_info::mem::init_disk_filter(&args);
+
+    let targets = build_targets(&args).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
 
     info!("成功读取参数: {args:?}");
 
-    loop {
-        let Ok(ws_stream) = connect_ws(
-            &connection_urls.ws_real_time,
-            args.tls,
-            args.ignore_unsafe_cert,
-        )
-        .await
-        else {
-            error!("无法连接到 Websocket 服务器，5 秒后重新尝试");
-            sleep(Duration::from_secs(5)).await;
-            continue;
-        };
+    let mut collector = Collector::new(&args);
+
+    let basic_info = Arc::new(BasicInfo::build(&collector.sysinfo_sys, &args.ip_provider).await);
+
+    let (args_sender, mut args_receiver) = watch::channel(args.clone());
+    #[cfg(unix)]
+    if args.config.is_some() {
+        tokio::spawn(config_file::reload_on_sighup(args_sender));
+    }
+    #[cfg(not(unix))]
+    drop(args_sender);
 
-        let (write, mut read) = ws_stream.split();
-
-        let locked_write: Arc<
-            Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>,
-        > = Arc::new(Mutex::new(write));
-
-        // Handle callbacks
-        {
-            let args_cloned = args.clone();
-            let connection_urls_cloned = connection_urls.clone();
-            let locked_write_cloned = locked_write.clone();
-            let _listener = tokio::spawn(async move {
-                handle_callbacks(
-                    &args_cloned,
-                    &connection_urls_cloned,
-                    &mut read,
-                    &locked_write_cloned,
-                )
-                .await;
-            });
+    // 每个主端独立维护连接，主循环每个周期只采集一次，再按各自的虚假倍率分发
+    let mut feeds = Vec::with_capacity(targets.len());
+    let mut target_tasks = JoinSet::new();
+    for target in targets {
+        let (sender, receiver) = mpsc::channel(TARGET_FEED_CAPACITY);
+        let (fake, fake_receiver) = watch::channel(target.fake);
+        feeds.push(TargetFeed {
+            name: target.name.clone(),
+            fake,
+            sender,
+        });
+        target_tasks.spawn(run_target(
+            target,
+            args.clone(),
+            basic_info.clone(),
+            receiver,
+            fake_receiver,
+        ));
+    }
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let signal = loop {
+        if refresh_args(&mut args, &mut args_receiver) {
+            collector.reconfigure(&args);
+            for feed in &feeds {
+                feed.update_fake(&args);
+            }
         }
+        let start_time = Instant::now();
+        let real_time = collector.collect();
 
-        let mut sysinfo_sys = sysinfo::System::new();
-        let mut networks = Networks::new_with_refreshed_list();
-        let mut disks = Disks::new();
-        sysinfo_sys.refresh_cpu_list(
-            CpuRefreshKind::nothing()
-                .without_cpu_usage()
-                .without_frequency(),
-        );
-        sysinfo_sys.refresh_memory_specifics(MemoryRefreshKind::everything());
-
-        let basic_info = BasicInfo::build(&sysinfo_sys, args.fake, &args.ip_provider).await;
-
-        basic_info.push(connection_urls.basic_info.clone(), args.ignore_unsafe_cert);
-
-        loop {
-            let start_time = tokio::time::Instant::now();
-            sysinfo_sys.refresh_specifics(
-                RefreshKind::nothing()
-                    .with_cpu(CpuRefreshKind::everything().without_frequency())
-                    .with_memory(MemoryRefreshKind::everything()),
-            );
-            networks.refresh(true);
-            disks.refresh_specifics(true, DiskRefreshKind::nothing().with_storage());
-            let real_time = RealTimeInfo::build(&sysinfo_sys, &networks, &disks, args.fake);
-
-            let json = json::to_string(&real_time);
-            {
-                let mut write = locked_write.lock().await;
-                if let Err(e) = write.send(Message::Text(Utf8Bytes::from(json))).await {
-                    error!("推送 RealTime 时发生错误，尝试重新连接: {e}");
-                    break;
-                }
+        for feed in &feeds {
+            let json = json::to_string(&real_time.with_fake(*feed.fake.borrow()));
+            if let Err(TrySendError::Full(_)) = feed.sender.try_send(json) {
+                warn!("[{}] 上报队列已满，丢弃本次样本", feed.name);
             }
-            let end_time = start_time.elapsed();
+        }
+
+        let end_time = start_time.elapsed();
+        let interval = Duration::from_millis({
+            let end = u64::try_from(end_time.as_millis()).unwrap_or(0);
+            args.realtime_info_interval.saturating_sub(end)
+        });
+        tokio::select! {
+            () = sleep(interval) => {}
+            signal = &mut shutdown => break signal,
+        }
+    };
 
-            sleep(Duration::from_millis({
-                let end = u64::try_from(end_time.as_millis()).unwrap_or(0);
-                args.realtime_info_interval.saturating_sub(end)
-            }))
-            .await;
+    // 停止采集并关闭上报队列，各主端任务发送 Close 帧，等待远程执行结果回传与终端会话关闭后退出
+    info!("收到 {signal}，正在关闭与主端的连接");
+    collector.flush();
+    drop(feeds);
+    let shutdown_timeout = Duration::from_secs(args.shutdown_timeout);
+    // 正常关闭时以 0 退出，避免 systemd 将主动停止记为失败
+    let exit_code = tokio::select! {
+        _ = target_tasks.join_all() => {
+            info!("已关闭与所有主端的连接");
+            0
         }
+        () = sleep(shutdown_timeout) => {
+            warn!("{} 秒内未能关闭所有连接，强制退出", shutdown_timeout.as_secs());
+            signal.exit_code()
+        }
+        signal = shutdown_signal() => {
+            warn!("再次收到 {signal}，强制退出");
+            signal.exit_code()
+        }
+    };
+    std::process::exit(exit_code);
+}
+
+/// 触发退出的信号
+#[derive(Debug, Clone, Copy)]
+enum ShutdownSignal {
+    Terminate,
+    Interrupt,
+}
+
+impl ShutdownSignal {
+    /// 未能正常关闭时按惯例以 128 + 信号编号退出
+    fn exit_code(self) -> i32 {
+        match self {
+            ShutdownSignal::Terminate => 143,
+            ShutdownSignal::Interrupt => 130,
+        }
+    }
+}
+
+impl fmt::Display for ShutdownSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownSignal::Terminate => write!(f, "SIGTERM"),
+            ShutdownSignal::Interrupt => write!(f, "SIGINT"),
+        }
+    }
+}
+
+/// 等待 SIGTERM 或 SIGINT (Ctrl+C)
+async fn shutdown_signal() -> ShutdownSignal {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+            warn!("无法监听 SIGTERM，仅响应 Ctrl+C");
+            let _ = tokio::signal::ctrl_c().await;
+            return ShutdownSignal::Interrupt;
+        };
+        tokio::select! {
+            _ = terminate.recv() => ShutdownSignal::Terminate,
+            _ = tokio::signal::ctrl_c() => ShutdownSignal::Interrupt,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        ShutdownSignal::Interrupt
+    }
+}
+
+/// 每个主端的上报队列长度，断线时样本由主端任务写入各自的离线缓冲区
+const TARGET_FEED_CAPACITY: usize = 16;
+
+struct TargetFeed {
+    name: String,
+    /// 虚假倍率，主端任务在其变化时重新上报 `BasicInfo`
+    fake: watch::Sender<f64>,
+    sender: mpsc::Sender<String>,
+}
+
+impl TargetFeed {
+    /// 应用重新加载后的虚假倍率，之后的样本与 `BasicInfo` 均使用新的倍率
+    fn update_fake(&self, args: &Args) {
+        let Some(fake) = target_fake(args, &self.name) else {
+            return;
+        };
+        self.fake.send_if_modified(|current| {
+            let changed = current.to_bits() != fake.to_bits();
+            if changed {
+                info!("[{}] 虚假倍率已修改为 {fake}", self.name);
+                *current = fake;
+            }
+            changed
+        });
+    }
+}
+
+/// 若配置已通过 SIGHUP 重新加载，则更新参数并应用可在运行时生效的设置
+fn refresh_args(args: &mut Args, receiver: &mut watch::Receiver<Args>) -> bool {
+    if receiver.has_changed().unwrap_or(false) {
+        *args = receiver.borrow_and_update().clone();
+        args.apply_runtime_settings();
+        info!("配置已重新加载: {args:?}");
+        true
+    } else {
+        false
     }
 }
@@ -0,0 +1,333 @@
+use crate::command_parser::Args;
+use crate::target::DEFAULT_TARGET;
+use log::{debug, error, info, warn};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone)]
+pub struct BufferedSample {
+    pub timestamp: u64,
+    pub json: String,
+}
+
+impl BufferedSample {
+    /// 回放时发送的内容，在 JSON 对象开头加入采集时间 (Unix 秒)，以便主端按原时间记录
+    pub fn payload(&self) -> String {
+        match self.json.strip_prefix('{') {
+            Some("}") => format!("{{\"timestamp\":{}}}", self.timestamp),
+            Some(rest) => format!("{{\"timestamp\":{},{rest}", self.timestamp),
+            None => self.json.clone(),
+        }
+    }
+}
+
+/// 断线期间的 `RealTimeInfo` 缓冲区
+///
+/// 内存中为定长环形队列，满了之后最旧的样本会被溢写到磁盘文件 (若配置)，否则直接丢弃；
+/// 重新连接后按时间顺序先回放磁盘中的样本，再回放内存中的样本
+#[derive(Debug)]
+pub struct OfflineBuffer {
+    entries: VecDeque<BufferedSample>,
+    max_entries: usize,
+    max_age: Duration,
+    spill_file: Option<PathBuf>,
+    spill_max_bytes: u64,
+    dropped: u64,
+}
+
+impl OfflineBuffer {
+    /// 每个主端使用独立的缓冲区，额外主端的溢写文件名会附加主端名称
+    pub fn new(args: &Args, name: &str) -> Self {
+        let spill_file = args.offline_buffer_file.as_ref().map(|path| {
+            if name == DEFAULT_TARGET {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(format!("{path}.{name}"))
+            }
+        });
+
+        Self {
+            entries: VecDeque::with_capacity(args.offline_buffer_size),
+            max_entries: args.offline_buffer_size,
+            max_age: Duration::from_secs(args.offline_buffer_max_age),
+            spill_file,
+            spill_max_bytes: args
+                .offline_buffer_file_max_size
+                .saturating_mul(1024 * 1024),
+            dropped: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_entries != 0
+    }
+
+    pub fn push(&mut self, json: String) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.evict_expired();
+
+        if self.entries.len() >= self.max_entries
+            && let Some(oldest) = self.entries.pop_front()
+            && !self.spill(&oldest)
+        {
+            self.dropped += 1;
+            debug!(
+                "离线缓冲区已满，丢弃最旧的样本 (累计丢弃 {} 条)",
+                self.dropped
+            );
+        }
+
+        self.entries.push_back(BufferedSample {
+            timestamp: now_secs(),
+            json,
+        });
+    }
+
+    /// 取出所有未过期的样本 (按时间顺序)，并清空内存与磁盘中的缓冲
+    pub fn take_all(&mut self) -> Vec<BufferedSample> {
+        let mut samples = self.take_spilled();
+        self.evict_expired();
+        samples.extend(self.entries.drain(..));
+
+        if !samples.is_empty() {
+            info!(
+                "离线缓冲区共有 {} 条样本待回放 (累计丢弃 {} 条)",
+                samples.len(),
+                self.dropped
+            );
+        }
+
+        samples
+    }
+
+    /// 将回放失败的样本放回缓冲区头部，等待下一次重连
+    pub fn restore(&mut self, mut samples: Vec<BufferedSample>) {
+        let keep = self
+            .max_entries
+            .saturating_sub(self.entries.len())
+            .min(samples.len());
+        let kept = samples.split_off(samples.len() - keep);
+
+        for sample in &samples {
+            if !self.spill(sample) {
+                self.dropped += 1;
+            }
+        }
+        for sample in kept.into_iter().rev() {
+            self.entries.push_front(sample);
+        }
+    }
+
+    fn evict_expired(&mut self) {
+        let now = now_secs();
+        let max_age = self.max_age.as_secs();
+        while let Some(front) = self.entries.front() {
+            if now.saturating_sub(front.timestamp) <= max_age {
+                break;
+            }
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    fn spill(&self, sample: &BufferedSample) -> bool {
+        let Some(path) = &self.spill_file else {
+            return false;
+        };
+
+        if fs::metadata(path).is_ok_and(|m| m.len() >= self.spill_max_bytes) {
+            warn!("离线缓冲文件 {} 已达到大小上限", path.display());
+            return false;
+        }
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}\t{}", sample.timestamp, sample.json));
+
+        if let Err(e) = result {
+            error!("无法写入离线缓冲文件 {}: {e}", path.display());
+            return false;
+        }
+
+        true
+    }
+
+    fn take_spilled(&mut self) -> Vec<BufferedSample> {
+        let Some(path) = self.spill_file.clone() else {
+            return Vec::new();
+        };
+
+        let Ok(file) = fs::File::open(&path) else {
+            return Vec::new();
+        };
+
+        let now = now_secs();
+        let max_age = self.max_age.as_secs();
+        let mut samples = Vec::new();
+
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let Some((timestamp, json)) = line.split_once('\t') else {
+                continue;
+            };
+            let Ok(timestamp) = timestamp.parse::<u64>() else {
+                continue;
+            };
+            if now.saturating_sub(timestamp) > max_age {
+                self.dropped += 1;
+                continue;
+            }
+            samples.push(BufferedSample {
+                timestamp,
+                json: json.to_string(),
+            });
+        }
+
+        if let Err(e) = fs::remove_file(&path) {
+            error!("无法删除离线缓冲文件 {}: {e}", path.display());
+        }
+
+        samples
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn buffer(extra: &[&str]) -> OfflineBuffer {
+        let mut argv = vec![
+            "komari-monitor-rs",
+            "--http-server",
+            "http://panel",
+            "-t",
+            "t",
+        ];
+        argv.extend_from_slice(extra);
+        OfflineBuffer::new(&Args::try_parse_from(argv).unwrap(), DEFAULT_TARGET)
+    }
+
+    fn jsons(samples: &[BufferedSample]) -> Vec<&str> {
+        samples.iter().map(|sample| sample.json.as_str()).collect()
+    }
+
+    #[test]
+    fn disabled_buffer_keeps_nothing() {
+        let mut buffer = buffer(&[]);
+        buffer.push("{}".to_string());
+        assert!(buffer.take_all().is_empty());
+    }
+
+    #[test]
+    fn full_buffer_drops_oldest_without_spill_file() {
+        let mut buffer = buffer(&["--offline-buffer-size", "2"]);
+        for i in 0..4 {
+            buffer.push(format!("{{\"n\":{i}}}"));
+        }
+        assert_eq!(jsons(&buffer.take_all()), ["{\"n\":2}", "{\"n\":3}"]);
+        assert_eq!(buffer.dropped, 2);
+        assert!(buffer.take_all().is_empty());
+    }
+
+    #[test]
+    fn spilled_samples_replay_before_memory_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spill");
+        let mut buffer = buffer(&[
+            "--offline-buffer-size",
+            "2",
+            "--offline-buffer-file",
+            path.to_str().unwrap(),
+        ]);
+        for i in 0..5 {
+            buffer.push(format!("{{\"n\":{i}}}"));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        let samples = buffer.take_all();
+        assert_eq!(
+            jsons(&samples),
+            [
+                "{\"n\":0}",
+                "{\"n\":1}",
+                "{\"n\":2}",
+                "{\"n\":3}",
+                "{\"n\":4}"
+            ]
+        );
+        assert!(!path.exists());
+        assert_eq!(buffer.dropped, 0);
+    }
+
+    #[test]
+    fn restore_keeps_newest_in_memory_and_spills_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spill");
+        let mut buffer = buffer(&[
+            "--offline-buffer-size",
+            "2",
+            "--offline-buffer-file",
+            path.to_str().unwrap(),
+        ]);
+        for i in 0..4 {
+            buffer.push(format!("{{\"n\":{i}}}"));
+        }
+        let mut samples = buffer.take_all();
+        // 第一条回放成功，其余放回缓冲区
+        samples.remove(0);
+        buffer.restore(samples);
+        buffer.push("{\"n\":4}".to_string());
+
+        assert_eq!(
+            jsons(&buffer.take_all()),
+            ["{\"n\":1}", "{\"n\":2}", "{\"n\":3}", "{\"n\":4}"]
+        );
+    }
+
+    #[test]
+    fn expired_spilled_samples_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spill");
+        fs::write(
+            &path,
+            format!("1\t{{\"old\":1}}\n{}\t{{\"new\":1}}\nbroken\n", now_secs()),
+        )
+        .unwrap();
+        let mut buffer = buffer(&[
+            "--offline-buffer-size",
+            "2",
+            "--offline-buffer-file",
+            path.to_str().unwrap(),
+        ]);
+
+        assert_eq!(jsons(&buffer.take_all()), ["{\"new\":1}"]);
+        assert_eq!(buffer.dropped, 1);
+    }
+
+    #[test]
+    fn payload_carries_capture_timestamp() {
+        let sample = |json: &str| BufferedSample {
+            timestamp: 1_700_000_000,
+            json: json.to_string(),
+        };
+        assert_eq!(
+            sample("{\"cpu\":1}").payload(),
+            "{\"timestamp\":1700000000,\"cpu\":1}"
+        );
+        assert_eq!(sample("{}").payload(), "{\"timestamp\":1700000000}");
+    }
+}
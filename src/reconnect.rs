@@ -0,0 +1,332 @@
+use crate::command_parser::Args;
+use log::{info, warn};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// 连接保持超过该时长后断开才重置退避，避免主端接受后立即关闭连接时所有 Agent 以初始间隔同步重试
+const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    BackingOff,
+    Degraded,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "connecting"),
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::BackingOff => write!(f, "backing-off"),
+            ConnectionState::Degraded => write!(f, "degraded"),
+        }
+    }
+}
+
+/// 带上限与抖动的指数退避
+///
+/// 第 n 次失败的基础等待为 `initial * 2^n`，不超过 `max`；
+/// 实际等待会在 `[base * (1 - jitter), base]` 内随机取值，避免大量 Agent 同时重连
+#[derive(Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    jitter: f64,
+    attempt: u32,
+    rng: u64,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration, jitter: f64) -> Self {
+        // 同一进程内的多个主端同时创建退避器时，用计数器区分随机种子
+        static INSTANCES: AtomicU64 = AtomicU64::new(0);
+        let instance = INSTANCES.fetch_add(1, Ordering::Relaxed);
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+            ^ (u64::from(std::process::id()) << 32)
+            ^ instance.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        // splitmix64 扰动，避免相近的种子产生相近的首个随机数
+        let seed = (seed ^ (seed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        let seed = (seed ^ (seed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        let seed = seed ^ (seed >> 31);
+
+        Self {
+            initial,
+            max: max.max(initial),
+            jitter: jitter.clamp(0.0, 1.0),
+            attempt: 0,
+            // xorshift 的状态不能为 0
+            rng: seed | 1,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self
+            .initial
+            .checked_mul(1u32.checked_shl(self.attempt).unwrap_or(u32::MAX))
+            .unwrap_or(self.max)
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let factor = 1.0 - self.jitter * self.next_random();
+        base.mul_f64(factor)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// xorshift64，返回 [0, 1) 之间的随机数
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// 主上报连接的状态机
+///
+/// 每次状态切换都会输出一行日志，并累计切换次数与失败次数作为简易指标
+#[derive(Debug)]
+pub struct ConnectionStateMachine {
+    name: String,
+    state: ConnectionState,
+    since: Instant,
+    /// 当前连接建立的时间，连接结束后清空
+    connected_at: Option<Instant>,
+    backoff: Backoff,
+    consecutive_failures: u32,
+    degraded_threshold: u32,
+    total_failures: u64,
+    transitions: u64,
+}
+
+impl ConnectionStateMachine {
+    pub fn new(args: &Args, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: ConnectionState::Connecting,
+            since: Instant::now(),
+            connected_at: None,
+            backoff: Backoff::new(
+                Duration::from_millis(args.reconnect_initial_delay),
+                Duration::from_millis(args.reconnect_max_delay),
+                args.reconnect_jitter,
+            ),
+            consecutive_failures: 0,
+            degraded_threshold: args.degraded_threshold,
+            total_failures: 0,
+            transitions: 0,
+        }
+    }
+
+    pub fn connecting(&mut self) {
+        self.connection_ended();
+        self.transition(ConnectionState::Connecting);
+    }
+
+    pub fn connected(&mut self) {
+        self.connected_at = Some(Instant::now());
+        self.transition(ConnectionState::Connected);
+    }
+
+    /// 连接失败或已建立的连接断开，返回下一次重连前需要等待的时间
+    pub fn failed(&mut self) -> Duration {
+        self.connection_ended();
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.total_failures += 1;
+
+        if self.degraded_threshold != 0 && self.consecutive_failures >= self.degraded_threshold {
+            self.transition(ConnectionState::Degraded);
+        } else {
+            self.transition(ConnectionState::BackingOff);
+        }
+
+        let delay = self.backoff.next_delay();
+        info!(
+            "[{}] 将在 {} ms 后重新连接 (连续失败 {} 次)",
+            self.name,
+            delay.as_millis(),
+            self.consecutive_failures
+        );
+        delay
+    }
+
+    /// 连接稳定保持过一段时间才视为恢复，清零连续失败次数并重置退避
+    fn connection_ended(&mut self) {
+        if self
+            .connected_at
+            .take()
+            .is_some_and(|connected_at| connected_at.elapsed() >= STABLE_CONNECTION)
+        {
+            self.consecutive_failures = 0;
+            self.backoff.reset();
+        }
+    }
+
+    fn transition(&mut self, to: ConnectionState) {
+        if self.state == to {
+            return;
+        }
+
+        let from = self.state;
+        let elapsed = self.since.elapsed();
+        self.state = to;
+        self.since = Instant::now();
+        self.transitions += 1;
+
+        let message = format!(
+            "[{}] 连接状态变更: {from} -> {to} (上一状态持续 {} ms, 累计切换 {} 次, 累计失败 {} 次)",
+            self.name,
+            elapsed.as_millis(),
+            self.transitions,
+            self.total_failures
+        );
+        if to == ConnectionState::Degraded {
+            warn!("{message}");
+        } else {
+            info!("{message}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn args(extra: &[&str]) -> Args {
+        let mut argv = vec![
+            "komari-monitor-rs",
+            "--http-server",
+            "http://panel",
+            "-t",
+            "t",
+        ];
+        argv.extend_from_slice(extra);
+        Args::try_parse_from(argv).unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_until_cap_without_jitter() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1), 0.0);
+        let delays: Vec<u128> = (0..6).map(|_| backoff.next_delay().as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn backoff_does_not_overflow_after_many_attempts() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_mins(1), 0.0);
+        for _ in 0..200 {
+            assert!(backoff.next_delay() <= Duration::from_mins(1));
+        }
+        assert_eq!(backoff.next_delay(), Duration::from_mins(1));
+    }
+
+    #[test]
+    fn backoff_jitter_stays_within_range() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(1), 0.5);
+        for _ in 0..1000 {
+            let delay = backoff.next_delay();
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn backoff_clamps_max_and_jitter() {
+        let mut backoff = Backoff::new(Duration::from_millis(500), Duration::from_millis(100), 7.0);
+        assert_eq!(backoff.max, Duration::from_millis(500));
+        assert!((backoff.jitter - 1.0).abs() < f64::EPSILON);
+        assert!(backoff.next_delay() <= Duration::from_millis(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn state_machine_degrades_after_threshold_and_recovers() {
+        let mut machine = ConnectionStateMachine::new(
+            &args(&["--reconnect-jitter", "0", "--degraded-threshold", "3"]),
+            "test",
+        );
+        assert_eq!(machine.state, ConnectionState::Connecting);
+
+        assert_eq!(machine.failed(), Duration::from_secs(1));
+        assert_eq!(machine.state, ConnectionState::BackingOff);
+        machine.connecting();
+        assert_eq!(machine.failed(), Duration::from_secs(2));
+        assert_eq!(machine.state, ConnectionState::BackingOff);
+        machine.connecting();
+        assert_eq!(machine.failed(), Duration::from_secs(4));
+        assert_eq!(machine.state, ConnectionState::Degraded);
+        assert_eq!(machine.total_failures, 3);
+
+        machine.connecting();
+        machine.connected();
+        assert_eq!(machine.state, ConnectionState::Connected);
+        tokio::time::advance(STABLE_CONNECTION).await;
+        // 连接稳定保持后断开，退避重新从初始值开始
+        assert_eq!(machine.failed(), Duration::from_secs(1));
+        assert_eq!(machine.consecutive_failures, 1);
+        assert_eq!(machine.state, ConnectionState::BackingOff);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn short_lived_connection_does_not_reset_backoff() {
+        let mut machine = ConnectionStateMachine::new(
+            &args(&["--reconnect-jitter", "0", "--degraded-threshold", "3"]),
+            "test",
+        );
+        assert_eq!(machine.failed(), Duration::from_secs(1));
+
+        // 主端接受连接后立即关闭，仍按连续失败继续退避
+        for expected in [2, 4] {
+            machine.connecting();
+            machine.connected();
+            tokio::time::advance(STABLE_CONNECTION / 2).await;
+            assert_eq!(machine.failed(), Duration::from_secs(expected));
+        }
+        assert_eq!(machine.state, ConnectionState::Degraded);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stable_connection_resets_when_switching_servers() {
+        let mut machine = ConnectionStateMachine::new(&args(&["--reconnect-jitter", "0"]), "test");
+        machine.failed();
+        machine.failed();
+        machine.connecting();
+        machine.connected();
+        tokio::time::advance(STABLE_CONNECTION).await;
+        // 回切主地址时不经过 failed，直接重新连接
+        machine.connecting();
+        assert_eq!(machine.consecutive_failures, 0);
+        assert_eq!(machine.failed(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn state_machine_without_degraded_threshold_never_degrades() {
+        let mut machine =
+            ConnectionStateMachine::new(&args(&["--degraded-threshold", "0"]), "test");
+        for _ in 0..20 {
+            machine.failed();
+            assert_eq!(machine.state, ConnectionState::BackingOff);
+        }
+    }
+
+    #[test]
+    fn repeated_transition_to_same_state_is_not_counted() {
+        let mut machine = ConnectionStateMachine::new(&args(&[]), "test");
+        machine.connecting();
+        assert_eq!(machine.transitions, 0);
+        machine.connected();
+        machine.connected();
+        assert_eq!(machine.transitions, 1);
+    }
+}
@@ -0,0 +1,313 @@
+use log::{info, warn};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{Instant, timeout_at};
+
+/// PTY 会话收到取消信号后终止子进程的等待时间
+const SESSION_TEARDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 进程退出前等待远程执行结果回传的时间
+const EXEC_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 取消信号，发送端被丢弃时同样视为已取消
+#[derive(Debug, Clone)]
+pub struct CancelSignal(watch::Receiver<bool>);
+
+impl CancelSignal {
+    pub async fn cancelled(&mut self) {
+        let _ = self.0.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// 单个上报连接派生的所有任务
+///
+/// 读取任务与 Ping 回复在连接失效时直接中止；PTY 会话收到取消信号后自行终止子进程。
+/// 远程执行的结果通过 HTTP 回传，不依赖上报连接，交由主端级别的 `ExecJobs` 管理
+#[derive(Debug)]
+pub struct ConnectionSupervisor {
+    name: String,
+    cancel: watch::Sender<bool>,
+    tasks: Mutex<JoinSet<()>>,
+    sessions: Mutex<JoinSet<()>>,
+    exec_jobs: Arc<ExecJobs>,
+}
+
+impl ConnectionSupervisor {
+    pub fn new(name: &str, exec_jobs: &Arc<ExecJobs>) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_string(),
+            cancel: watch::Sender::new(false),
+            tasks: Mutex::new(JoinSet::new()),
+            sessions: Mutex::new(JoinSet::new()),
+            exec_jobs: exec_jobs.clone(),
+        })
+    }
+
+    pub fn cancel_signal(&self) -> CancelSignal {
+        CancelSignal(self.cancel.subscribe())
+    }
+
+    /// 可以随时中止的任务 (读取任务、Ping 回复、回切探测等)
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_into(&self.tasks, future);
+    }
+
+    /// PTY 会话，需要响应取消信号并终止子进程
+    pub fn spawn_session<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if !self.spawn_into(&self.sessions, future) {
+            warn!("[{}] 连接已关闭，拒绝新的终端会话", self.name);
+        }
+    }
+
+    /// 远程执行，连接关闭后继续运行并回传结果，取消信号来自 `ExecJobs`
+    pub fn spawn_exec<F>(&self, job: impl FnOnce(CancelSignal) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.exec_jobs.spawn(job);
+    }
+
+    /// 取消并回收该连接的读取任务与终端会话，远程执行任务不受影响
+    pub async fn shutdown(&self) {
+        self.cancel.send_replace(true);
+
+        let mut tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let mut sessions = std::mem::take(&mut *self.sessions.lock().unwrap());
+
+        tasks.shutdown().await;
+
+        let pending_sessions = drain(&mut sessions, SESSION_TEARDOWN_TIMEOUT).await;
+        if pending_sessions > 0 {
+            warn!(
+                "[{}] {pending_sessions} 个终端会话未能按时关闭，强制中止",
+                self.name
+            );
+            sessions.shutdown().await;
+        }
+    }
+
+    fn spawn_into<F>(&self, set: &Mutex<JoinSet<()>>, future: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        spawn_unless_cancelled(&self.cancel, set, future)
+    }
+}
+
+/// 单个主端的远程执行任务，跨越重连继续运行，仅在进程退出时取消
+///
+/// 收到取消信号后任务自行终止子进程，结果仍会回传给主端，关闭时会等待其完成
+#[derive(Debug)]
+pub struct ExecJobs {
+    name: String,
+    cancel: watch::Sender<bool>,
+    jobs: Mutex<JoinSet<()>>,
+}
+
+impl ExecJobs {
+    pub fn new(name: &str) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_string(),
+            cancel: watch::Sender::new(false),
+            jobs: Mutex::new(JoinSet::new()),
+        })
+    }
+
+    fn spawn<F>(&self, job: impl FnOnce(CancelSignal) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let future = job(CancelSignal(self.cancel.subscribe()));
+        if !spawn_unless_cancelled(&self.cancel, &self.jobs, future) {
+            warn!("[{}] 正在退出，拒绝新的远程执行任务", self.name);
+        }
+    }
+
+    /// 取消所有远程执行任务，并在期限内等待结果回传
+    pub async fn shutdown(&self) {
+        self.cancel.send_replace(true);
+
+        let mut jobs = std::mem::take(&mut *self.jobs.lock().unwrap());
+        let pending = jobs.len();
+        if pending > 0 {
+            info!("[{}] 等待 {pending} 个远程执行任务回传结果", self.name);
+        }
+        let pending = drain(&mut jobs, EXEC_DRAIN_TIMEOUT).await;
+        if pending > 0 {
+            warn!(
+                "[{}] {pending} 个远程执行任务未能按时回传结果，强制中止",
+                self.name
+            );
+            jobs.shutdown().await;
+        }
+    }
+}
+
+/// 已取消时不再派生新任务，返回是否已派生
+fn spawn_unless_cancelled<F>(
+    cancel: &watch::Sender<bool>,
+    set: &Mutex<JoinSet<()>>,
+    future: F,
+) -> bool
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    // 在持有锁时检查取消状态，保证 shutdown 取走任务集合后不会再有任务加入
+    let mut set = set.lock().unwrap();
+    if *cancel.borrow() {
+        return false;
+    }
+    // 顺便回收已结束的任务，避免长连接期间 JoinSet 无限增长
+    while set.try_join_next().is_some() {}
+    set.spawn(future);
+    true
+}
+
+/// 在期限内等待任务结束，返回仍未结束的任务数
+async fn drain(set: &mut JoinSet<()>, limit: Duration) -> usize {
+    let deadline = Instant::now() + limit;
+    while !set.is_empty() {
+        match timeout_at(deadline, set.join_next()).await {
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            Err(_) => return set.len(),
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::pending;
+    use tokio::time::sleep;
+
+    type Events = Arc<Mutex<Vec<&'static str>>>;
+
+    /// 被丢弃 (任务结束或被中止) 时记录事件
+    struct OnDrop(Events, &'static str);
+
+    impl Drop for OnDrop {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push(self.1);
+        }
+    }
+
+    fn record(events: &Events, event: &'static str) {
+        events.lock().unwrap().push(event);
+    }
+
+    fn events(events: &Events) -> Vec<&'static str> {
+        events.lock().unwrap().clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_aborts_tasks_then_waits_for_sessions() {
+        let log = Events::default();
+        let supervisor = ConnectionSupervisor::new("test", &ExecJobs::new("test"));
+
+        let guard = OnDrop(log.clone(), "task aborted");
+        supervisor.spawn(async move {
+            let _guard = guard;
+            pending::<()>().await;
+        });
+        let mut cancel = supervisor.cancel_signal();
+        let session_log = log.clone();
+        supervisor.spawn_session(async move {
+            cancel.cancelled().await;
+            sleep(Duration::from_secs(1)).await;
+            record(&session_log, "session closed");
+        });
+        tokio::task::yield_now().await;
+
+        supervisor.shutdown().await;
+        assert_eq!(events(&log), ["task aborted", "session closed"]);
+
+        // 关闭后不再接受新的会话
+        let late_log = log.clone();
+        supervisor.spawn_session(async move { record(&late_log, "late session") });
+        tokio::task::yield_now().await;
+        assert_eq!(events(&log), ["task aborted", "session closed"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stuck_session_is_aborted_after_timeout() {
+        let log = Events::default();
+        let supervisor = ConnectionSupervisor::new("test", &ExecJobs::new("test"));
+        let guard = OnDrop(log.clone(), "session aborted");
+        supervisor.spawn_session(async move {
+            let _guard = guard;
+            pending::<()>().await;
+        });
+
+        let start = Instant::now();
+        supervisor.shutdown().await;
+        assert_eq!(start.elapsed(), SESSION_TEARDOWN_TIMEOUT);
+        assert_eq!(events(&log), ["session aborted"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn exec_jobs_outlive_connection_shutdown() {
+        let log = Events::default();
+        let exec_jobs = ExecJobs::new("test");
+        let supervisor = ConnectionSupervisor::new("test", &exec_jobs);
+
+        let exec_log = log.clone();
+        supervisor.spawn_exec(|mut cancel| async move {
+            tokio::select! {
+                () = sleep(Duration::from_secs(30)) => record(&exec_log, "exec finished"),
+                () = cancel.cancelled() => record(&exec_log, "exec cancelled"),
+            }
+        });
+        tokio::task::yield_now().await;
+        supervisor.shutdown().await;
+        drop(supervisor);
+        assert!(events(&log).is_empty());
+
+        // 重连后的新连接共用同一组远程执行任务
+        let reconnected = ConnectionSupervisor::new("test", &exec_jobs);
+        let second_log = log.clone();
+        reconnected.spawn_exec(|mut cancel| async move {
+            cancel.cancelled().await;
+            sleep(Duration::from_secs(1)).await;
+            record(&second_log, "exec reported");
+        });
+
+        sleep(Duration::from_secs(31)).await;
+        assert_eq!(events(&log), ["exec finished"]);
+
+        exec_jobs.shutdown().await;
+        assert_eq!(events(&log), ["exec finished", "exec reported"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn exec_shutdown_aborts_stuck_jobs_and_rejects_new_ones() {
+        let log = Events::default();
+        let exec_jobs = ExecJobs::new("test");
+        let supervisor = ConnectionSupervisor::new("test", &exec_jobs);
+        let guard = OnDrop(log.clone(), "exec aborted");
+        supervisor.spawn_exec(|_| async move {
+            let _guard = guard;
+            pending::<()>().await;
+        });
+
+        let start = Instant::now();
+        exec_jobs.shutdown().await;
+        assert_eq!(start.elapsed(), EXEC_DRAIN_TIMEOUT);
+        assert_eq!(events(&log), ["exec aborted"]);
+
+        let late_log = log.clone();
+        supervisor.spawn_exec(|_| async move { record(&late_log, "late exec") });
+        tokio::task::yield_now().await;
+        assert_eq!(events(&log), ["exec aborted"]);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::auth::Secret;
+use crate::callbacks::handle_callbacks;
+use crate::command_parser::{Args, AuthMode, TargetArgs};
+use crate::data_struct::BasicInfo;
+use crate::failover::{ServerPool, probe_server};
+use crate::liveness::LivenessMonitor;
+use crate::offline_buffer::OfflineBuffer;
+use crate::reconnect::ConnectionStateMachine;
+use crate::supervisor::{ConnectionSupervisor, ExecJobs};
+use crate::utils::{ConnectionUrls, build_server_list, connect_ws};
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use log::{error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, mpsc, watch};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{Instant, interval, timeout};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::{Bytes, Message, Utf8Bytes};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// 存活检测与读取任务状态的检查间隔
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 命令行参数中的主端名称
+pub const DEFAULT_TARGET: &str = "default";
+
+/// 一个独立上报的主端，可包含多个故障转移地址
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub servers: Vec<ConnectionUrls>,
+    pub fake: f64,
+    pub terminal: bool,
+    pub exec: bool,
+}
+
+impl Target {
+    fn new(
+        name: &str,
+        http_server: &[String],
+        ws_server: &[String],
+        token: &Secret,
+        auth_mode: &AuthMode,
+    ) -> Result<Self, String> {
+        let servers = build_server_list(http_server, ws_server, token, auth_mode)
+            .map_err(|e| format!("主端 {name} 的地址无效: {e}"))?;
+
+        Ok(Self {
+            name: name.to_string(),
+            servers,
+            fake: 1.0,
+            terminal: false,
+            exec: false,
+        })
+    }
+}
+
+/// 根据参数构造所有主端，第一个为命令行参数中的主端
+pub fn build_targets(args: &Args) -> Result<Vec<Target>, String> {
+    let mut primary = Target::new(
+        DEFAULT_TARGET,
+        &args.http_server,
+        &args.ws_server,
+        &args.token,
+        &args.auth_mode,
+    )?;
+    primary.fake = args.fake;
+    primary.terminal = args.terminal;
+    primary.exec = args.terminal;
+
+    let mut targets = vec![primary];
+    for target_args in &args.targets {
+        targets.push(build_target(target_args)?);
+    }
+
+    if let Some(name) = targets.iter().enumerate().find_map(|(i, t)| {
+        targets[..i]
+            .iter()
+            .any(|o| o.name == t.name)
+            .then_some(&t.name)
+    }) {
+        return Err(format!("主端名称 {name} 重复"));
+    }
+
+    Ok(targets)
+}
+
+fn build_target(target_args: &TargetArgs) -> Result<Target, String> {
+    let mut target = Target::new(
+        &target_args.name,
+        &target_args.http_server,
+        &target_args.ws_server,
+        &target_args.token,
+        &target_args.auth_mode,
+    )?;
+    target.fake = target_args.fake;
+    target.terminal = target_args.terminal;
+    target.exec = target_args.exec;
+    Ok(target)
+}
+
+/// 参数中指定主端的虚假倍率，主端已不在参数中时返回 None
+pub fn target_fake(args: &Args, name: &str) -> Option<f64> {
+    if name == DEFAULT_TARGET {
+        return Some(args.fake);
+    }
+    args.targets
+        .iter()
+        .find(|target| target.name == name)
+        .map(|target| target.fake)
+}
+
+type LockedWriter = Arc<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>;
+
+/// 维护与单个主端的连接，按顺序上报主循环发来的样本
+///
+/// 断线期间收到的样本写入该主端独立的离线缓冲区，重连后回放；通道关闭时退出。
+/// 虚假倍率变化时在当前连接上重新上报 `BasicInfo`
+pub async fn run_target(
+    target: Target,
+    args: Args,
+    basic_info: Arc<BasicInfo>,
+    mut samples: mpsc::Receiver<String>,
+    mut fake: watch::Receiver<f64>,
+) {
+    let name = target.name.clone();
+    let mut state_machine = ConnectionStateMachine::new(&args, &name);
+    let mut offline_buffer = OfflineBuffer::new(&args, &name);
+    let mut server_pool = ServerPool::new(&name, target.servers.clone(), &args);
+    // 已失效连接的收尾任务，退出前等待其完成
+    let mut retiring = JoinSet::new();
+    // 远程执行的结果通过 HTTP 回传，重连时不中断，退出时才取消
+    let exec_jobs = ExecJobs::new(&name);
+
+    loop {
+        state_machine.connecting();
+        let connection_urls = server_pool.current().clone();
+        let ws_stream = match connect_ws(&connection_urls.ws_real_time, &connection_urls.auth).await
+        {
+            Ok(ws_stream) => ws_stream,
+            Err(e) => {
+                error!("[{name}] 无法连接到 Websocket 服务器: {e}");
+                server_pool.failed();
+                let delay = state_machine.failed();
+                if !wait_and_buffer(delay, &mut samples, &mut offline_buffer).await {
+                    finish(retiring, &exec_jobs).await;
+                    return;
+                }
+                continue;
+            }
+        };
+        state_machine.connected();
+        server_pool.connected();
+
+        let (write, mut read) = ws_stream.split();
+
+        let locked_write: LockedWriter = Arc::new(Mutex::new(write));
+        let mut liveness_monitor = LivenessMonitor::new(&args);
+
+        let supervisor = ConnectionSupervisor::new(&name, &exec_jobs);
+        let liveness = liveness_monitor.liveness();
+
+        // Handle callbacks
+        {
+            let target_cloned = target.clone();
+            let terminal_entry = args.terminal_entry.clone();
+            let connection_urls_cloned = connection_urls.clone();
+            let liveness = liveness.clone();
+            let supervisor_cloned = supervisor.clone();
+            let locked_write_cloned = locked_write.clone();
+            supervisor.spawn(async move {
+                handle_callbacks(
+                    &target_cloned,
+                    &terminal_entry,
+                    &connection_urls_cloned,
+                    &liveness,
+                    &supervisor_cloned,
+                    &mut read,
+                    &locked_write_cloned,
+                )
+                .await;
+                liveness.close_reader();
+            });
+        }
+
+        basic_info
+            .with_fake(*fake.borrow_and_update())
+            .push(&connection_urls.basic_info, &connection_urls.auth);
+
+        let mut failing_back = false;
+        let mut failback_probe: Option<JoinHandle<bool>> = None;
+
+        let send_timeout = liveness_monitor.send_timeout();
+        let mut liveness_check = interval(LIVENESS_CHECK_INTERVAL);
+
+        if replay_offline_samples(&name, &locked_write, &mut offline_buffer, send_timeout).await {
+            loop {
+                let json = tokio::select! {
+                    sample = samples.recv() => sample,
+                    Ok(()) = fake.changed() => {
+                        basic_info
+                            .with_fake(*fake.borrow_and_update())
+                            .push(&connection_urls.basic_info, &connection_urls.auth);
+                        continue;
+                    }
+                    _ = liveness_check.tick() => {
+                        if liveness.is_reader_closed() {
+                            warn!("[{name}] 读取任务已退出，连接可能已被主端关闭，尝试重新连接");
+                            break;
+                        }
+                        match liveness_monitor.poll() {
+                            Ok(true) => {
+                                let mut write = locked_write.lock().await;
+                                let ping = write.send(Message::Ping(Bytes::new()));
+                                if !matches!(timeout(send_timeout, ping).await, Ok(Ok(()))) {
+                                    warn!("[{name}] 发送 Ping 失败，尝试重新连接");
+                                    break;
+                                }
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                warn!("[{name}] {e}，连接可能已失效，尝试重新连接");
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                };
+                let Some(json) = json else {
+                    send_close_frame(&name, &locked_write, send_timeout).await;
+                    supervisor.shutdown().await;
+                    retire(&mut retiring, failback_probe);
+                    finish(retiring, &exec_jobs).await;
+                    return;
+                };
+
+                if failback_probe.as_ref().is_some_and(JoinHandle::is_finished)
+                    && let Some(probe) = failback_probe.take()
+                    && probe.await.unwrap_or(false)
+                {
+                    server_pool.fail_back();
+                    failing_back = true;
+                    offline_buffer.push(json);
+                    let _ = locked_write.lock().await.close().await;
+                    break;
+                }
+                if failback_probe.is_none()
+                    && let Some(primary) = server_pool.failback_probe_target()
+                {
+                    failback_probe = Some(tokio::spawn(probe_server(primary)));
+                }
+
+                let mut write = locked_write.lock().await;
+                let send = write.send(Message::Text(Utf8Bytes::from(json.as_str())));
+                match timeout(send_timeout, send).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        error!("[{name}] 推送 RealTime 时发生错误，尝试重新连接: {e}");
+                        offline_buffer.push(json);
+                        break;
+                    }
+                    Err(_) => {
+                        error!("[{name}] 推送 RealTime 超时，尝试重新连接");
+                        offline_buffer.push(json);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // 读取任务持有连接的另一半，必须结束它才能真正释放失效的连接；
+        // 终端会话的收尾在后台进行，不阻塞重连
+        retire(&mut retiring, failback_probe);
+        retiring.spawn(async move { supervisor.shutdown().await });
+
+        if failing_back {
+            continue;
+        }
+
+        let delay = state_machine.failed();
+        if !wait_and_buffer(delay, &mut samples, &mut offline_buffer).await {
+            finish(retiring, &exec_jobs).await;
+            return;
+        }
+    }
+}
+
+/// 中止未完成的回切探测，并回收已结束的收尾任务
+fn retire(retiring: &mut JoinSet<()>, failback_probe: Option<JoinHandle<bool>>) {
+    if let Some(probe) = failback_probe {
+        probe.abort();
+    }
+    while retiring.try_join_next().is_some() {}
+}
+
+/// 等待失效连接收尾，再取消仍在运行的远程执行任务并等待结果回传
+async fn finish(retiring: JoinSet<()>, exec_jobs: &ExecJobs) {
+    retiring.join_all().await;
+    exec_jobs.shutdown().await;
+}
+
+/// 进程退出前通知主端本机即将离线
+async fn send_close_frame(name: &str, locked_write: &LockedWriter, send_timeout: Duration) {
+    let frame = CloseFrame {
+        code: CloseCode::Away,
+        reason: Utf8Bytes::from_static("agent shutting down"),
+    };
+    let mut write = locked_write.lock().await;
+    match timeout(send_timeout, write.send(Message::Close(Some(frame)))).await {
+        Ok(Ok(())) => info!("[{name}] 已通知主端本机即将离线"),
+        Ok(Err(e)) => warn!("[{name}] 发送 Close 帧失败: {e}"),
+        Err(_) => warn!("[{name}] 发送 Close 帧超时"),
+    }
+}
+
+/// 按顺序回放离线缓冲区中的样本，全部发送成功时返回 true
+///
+/// 每条样本单独加锁并设置发送超时，连接阻塞时不会长期占用写入端
+async fn replay_offline_samples(
+    name: &str,
+    locked_write: &LockedWriter,
+    offline_buffer: &mut OfflineBuffer,
+    send_timeout: Duration,
+) -> bool {
+    let samples = offline_buffer.take_all();
+    if samples.is_empty() {
+        return true;
+    }
+
+    let total = samples.len();
+    for (index, sample) in samples.iter().enumerate() {
+        let mut write = locked_write.lock().await;
+        let send = write.send(Message::Text(Utf8Bytes::from(sample.payload())));
+        let error = match timeout(send_timeout, send).await {
+            Ok(Ok(())) => continue,
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "发送超时".to_string(),
+        };
+        error!("[{name}] 回放离线样本时发生错误，尝试重新连接: {error}");
+        offline_buffer.restore(samples[index..].to_vec());
+        return false;
+    }
+
+    info!("[{name}] 已回放 {total} 条离线样本");
+    true
+}
+
+/// 重连等待期间继续接收样本并写入离线缓冲区，通道关闭时返回 false
+async fn wait_and_buffer(
+    delay: Duration,
+    samples: &mut mpsc::Receiver<String>,
+    offline_buffer: &mut OfflineBuffer,
+) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        match timeout(
+            deadline.saturating_duration_since(Instant::now()),
+            samples.recv(),
+        )
+        .await
+        {
+            Ok(Some(json)) => offline_buffer.push(json),
+            Ok(None) => return false,
+            Err(_) => return true,
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use crate::command_parser::{Args, HistoryPeriod, TrafficQuery};
+use crate::get_info::diskstats::{DeviceSelector, disk_totals};
+use crate::traffic_store::{Boot, Counters, StoreFile, Usage, read_file, trim};
+use log::{error, info, warn};
+use miniserde::{Serialize, json};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::Path;
+use sysinfo::NetworkData;
+use time::{OffsetDateTime, UtcOffset};
+
+/// 各粒度保留的记录数
+const KEPT_HOURS: usize = 96;
+const KEPT_DAYS: usize = 62;
+const KEPT_MONTHS: usize = 25;
+
+const NETWORK_PREFIX: &str = "net:";
+const DISK_PREFIX: &str = "disk:";
+
+/// 时间段 -> 序列 (`net:<网卡>` 或 `disk:<设备>`) -> 用量
+type Buckets = BTreeMap<String, BTreeMap<String, Usage>>;
+
+/// 本地流量历史，按小时、天与月汇总每块网卡与磁盘的用量
+///
+/// 网卡的 up/down 为上传/下载，磁盘的 up/down 为写入/读取。文件格式与流量账本相同，
+/// 为制表符分隔的文本，可由 `traffic` 子命令查询
+#[derive(Debug)]
+pub struct TrafficHistory {
+    file: StoreFile,
+    offset: UtcOffset,
+    disks: DeviceSelector,
+    counters: Counters,
+    records: Records,
+}
+
+#[derive(Debug, Default)]
+struct Records {
+    boot: Boot,
+    counters: HashMap<String, Usage>,
+    hours: Buckets,
+    days: Buckets,
+    months: Buckets,
+}
+
+impl TrafficHistory {
+    pub fn new(args: &Args, path: &str) -> Self {
+        let file = StoreFile::new(path, "流量历史");
+        let mut counters = Counters::new(Boot::current());
+        let records = match file.read() {
+            Ok(Some(content)) => {
+                let mut records = Records::parse(&content);
+                if !counters.restore(&records.boot, std::mem::take(&mut records.counters)) {
+                    info!("系统已重启，流量历史将从当前计数开始累计");
+                }
+                records
+            }
+            Ok(None) => Records::default(),
+            Err(e) => {
+                error!("{e}");
+                Records::default()
+            }
+        };
+
+        info!("流量历史: {}", file.path().display());
+        Self {
+            file,
+            offset: OffsetDateTime::now_local().map_or(UtcOffset::UTC, OffsetDateTime::offset),
+            disks: DeviceSelector::new(&args.disk_io_devices),
+            counters,
+            records,
+        }
+    }
+
+    pub fn reconfigure(&mut self, args: &Args) {
+        self.disks = DeviceSelector::new(&args.disk_io_devices);
+    }
+
+    /// 累加本周期网卡与磁盘的用量，并按间隔写入磁盘
+    pub fn record(&mut self, selected: &[(&str, &NetworkData)]) {
+        let mut samples: Vec<(String, Usage)> = selected
+            .iter()
+            .map(|(name, data)| {
+                let usage = Usage {
+                    up: data.total_transmitted(),
+                    down: data.total_received(),
+                };
+                (format!("{NETWORK_PREFIX}{name}"), usage)
+            })
+            .collect();
+        samples.extend(
+            disk_totals(&mut self.disks)
+                .into_iter()
+                .map(|(name, read, written)| {
+                    let usage = Usage {
+                        up: written,
+                        down: read,
+                    };
+                    (format!("{DISK_PREFIX}{name}"), usage)
+                }),
+        );
+
+        let increments = self.counters.update(samples);
+        self.records
+            .add(OffsetDateTime::now_utc().to_offset(self.offset), increments);
+
+        self.file.changed(|| self.records.content(&self.counters));
+    }
+
+    pub fn save(&mut self) {
+        self.file.save(|| self.records.content(&self.counters));
+    }
+}
+
+impl Records {
+    /// 把各序列的增量计入所在的小时、天与月
+    fn add(&mut self, now: OffsetDateTime, increments: Vec<(String, Usage)>) {
+        let hour = format!("{} {:02}:00", now.date(), now.hour());
+        let day = now.date().to_string();
+        let month = format!("{}-{:02}", now.year(), u8::from(now.month()));
+
+        for (series, increment) in increments {
+            if increment == Usage::default() {
+                continue;
+            }
+            for (buckets, key) in [
+                (&mut self.hours, &hour),
+                (&mut self.days, &day),
+                (&mut self.months, &month),
+            ] {
+                buckets
+                    .entry(key.clone())
+                    .or_default()
+                    .entry(series.clone())
+                    .or_default()
+                    .add(increment);
+            }
+        }
+        trim(&mut self.hours, KEPT_HOURS);
+        trim(&mut self.days, KEPT_DAYS);
+        trim(&mut self.months, KEPT_MONTHS);
+    }
+
+    fn content(&self, counters: &Counters) -> String {
+        let mut content = String::new();
+        counters.write(&mut content);
+        for (kind, buckets) in [
+            ("hour", &self.hours),
+            ("day", &self.days),
+            ("month", &self.months),
+        ] {
+            for (time, series) in buckets {
+                for (name, usage) in series {
+                    let _ = writeln!(
+                        content,
+                        "{kind}\t{time}\t{name}\t{}\t{}",
+                        usage.up, usage.down
+                    );
+                }
+            }
+        }
+        content
+    }
+
+    fn parse(content: &str) -> Self {
+        let mut records = Self::default();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let parsed = match fields.as_slice() {
+                [key @ ("boot_id" | "boot_time"), value] => records.boot.parse(key, value),
+                ["counter", series, up, down] => Usage::parse(up, down)
+                    .map(|usage| records.counters.insert((*series).to_string(), usage))
+                    .is_some(),
+                [kind, time, series, up, down] => {
+                    let buckets = match *kind {
+                        "hour" => &mut records.hours,
+                        "day" => &mut records.days,
+                        "month" => &mut records.months,
+                        _ => {
+                            warn!("流量历史中存在无效的记录: {line}");
+                            continue;
+                        }
+                    };
+                    Usage::parse(up, down)
+                        .map(|usage| {
+                            buckets
+                                .entry((*time).to_string())
+                                .or_default()
+                                .insert((*series).to_string(), usage)
+                        })
+                        .is_some()
+                }
+                _ => false,
+            };
+            if !parsed {
+                warn!("流量历史中存在无效的记录: {line}");
+            }
+        }
+        records
+    }
+}
+
+#[derive(Serialize)]
+struct NetworkEntry {
+    time: String,
+    up: u64,
+    down: u64,
+    total: u64,
+}
+
+#[derive(Serialize)]
+struct DiskEntry {
+    time: String,
+    read: u64,
+    write: u64,
+    total: u64,
+}
+
+#[derive(Serialize)]
+struct QueryOutput<T> {
+    name: String,
+    period: String,
+    entries: Vec<T>,
+}
+
+/// `traffic` 子命令: 打印指定网卡 (未指定时为所有网卡的合计) 或磁盘的历史用量
+///
+/// 运行中的 Agent 每分钟写入一次文件，最近一分钟内的用量可能尚未写入
+pub fn run_query(args: &Args, query: &TrafficQuery) -> Result<(), String> {
+    let Some(path) = &args.traffic_history_file else {
+        return Err("未设置 --traffic-history-file".to_string());
+    };
+    let records = read_file(Path::new(path), "流量历史")?
+        .map(|content| Records::parse(&content))
+        .ok_or_else(|| format!("流量历史 {path} 不存在"))?;
+
+    let buckets = match query.period {
+        HistoryPeriod::Hourly => &records.hours,
+        HistoryPeriod::Daily => &records.days,
+        HistoryPeriod::Monthly => &records.months,
+    };
+    // 未指定网卡与磁盘时合计所有网卡
+    let (name, wanted) = match (&query.interface, &query.disk) {
+        (_, Some(disk)) => (disk.clone(), Some(format!("{DISK_PREFIX}{disk}"))),
+        (Some(interface), None) => (
+            interface.clone(),
+            Some(format!("{NETWORK_PREFIX}{interface}")),
+        ),
+        (None, None) => ("all".to_string(), None),
+    };
+    let matches = |series: &str| {
+        wanted
+            .as_deref()
+            .map_or(series.starts_with(NETWORK_PREFIX), |wanted| {
+                series == wanted
+            })
+    };
+
+    let mut rows: Vec<(String, Usage)> = buckets
+        .iter()
+        .filter_map(|(time, series)| {
+            let mut usage = Usage::default();
+            let mut found = false;
+            for (_, series_usage) in series.iter().filter(|(s, _)| matches(s)) {
+                usage.add(*series_usage);
+                found = true;
+            }
+            found.then(|| (time.clone(), usage))
+        })
+        .collect();
+    if query.limit > 0 && rows.len() > query.limit {
+        rows.drain(..rows.len() - query.limit);
+    }
+
+    let is_disk = query.disk.is_some();
+    if query.json {
+        let period = query.period.to_string();
+        let output = if is_disk {
+            json::to_string(&QueryOutput {
+                name,
+                period,
+                entries: rows
+                    .into_iter()
+                    .map(|(time, usage)| DiskEntry {
+                        time,
+                        read: usage.down,
+                        write: usage.up,
+                        total: usage.up.saturating_add(usage.down),
+                    })
+                    .collect(),
+            })
+        } else {
+            json::to_string(&QueryOutput {
+                name,
+                period,
+                entries: rows
+                    .into_iter()
+                    .map(|(time, usage)| NetworkEntry {
+                        time,
+                        up: usage.up,
+                        down: usage.down,
+                        total: usage.up.saturating_add(usage.down),
+                    })
+                    .collect(),
+            })
+        };
+        println!("{output}");
+        return Ok(());
+    }
+
+    let (first, second) = if is_disk {
+        ("读取", "写入")
+    } else {
+        ("下载", "上传")
+    };
+    println!("{name} ({})", query.period);
+    println!(
+        "{}{}{}{}",
+        pad("时间", 18, false),
+        pad(first, 14, true),
+        pad(second, 14, true),
+        pad("合计", 14, true)
+    );
+    if rows.is_empty() {
+        println!("暂无记录");
+    }
+    let mut sum = Usage::default();
+    for (time, usage) in &rows {
+        sum.add(*usage);
+        print_row(time, usage);
+    }
+    if rows.len() > 1 {
+        print_row("合计", &sum);
+    }
+    Ok(())
+}
+
+fn print_row(time: &str, usage: &Usage) {
+    println!(
+        "{}{:>14}{:>14}{:>14}",
+        pad(time, 18, false),
+        human_bytes(usage.down),
+        human_bytes(usage.up),
+        human_bytes(usage.up.saturating_add(usage.down))
+    );
+}
+
+/// 按终端显示宽度对齐，中文字符占两列
+fn pad(text: &str, width: usize, right: bool) -> String {
+    let display_width: usize = text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum();
+    let padding = " ".repeat(width.saturating_sub(display_width));
+    if right {
+        format!("{padding}{text}")
+    } else {
+        format!("{text}{padding}")
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.2} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month};
+
+    fn at(year: i32, month: Month, day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    fn usage(up: u64, down: u64) -> Usage {
+        Usage { up, down }
+    }
+
+    fn increments(entries: &[(&str, Usage)]) -> Vec<(String, Usage)> {
+        entries
+            .iter()
+            .map(|(series, usage)| ((*series).to_string(), *usage))
+            .collect()
+    }
+
+    fn bucket(buckets: &Buckets, time: &str, series: &str) -> Option<Usage> {
+        buckets.get(time)?.get(series).copied()
+    }
+
+    #[test]
+    fn hourly_rollup_splits_by_hour() {
+        let mut records = Records::default();
+        records.add(
+            at(2025, Month::March, 1, 9, 10),
+            increments(&[("net:eth0", usage(1, 10)), ("disk:sda", usage(7, 0))]),
+        );
+        records.add(
+            at(2025, Month::March, 1, 9, 59),
+            increments(&[("net:eth0", usage(2, 20))]),
+        );
+        records.add(
+            at(2025, Month::March, 1, 10, 0),
+            increments(&[("net:eth0", usage(4, 40))]),
+        );
+
+        assert_eq!(
+            bucket(&records.hours, "2025-03-01 09:00", "net:eth0"),
+            Some(usage(3, 30))
+        );
+        assert_eq!(
+            bucket(&records.hours, "2025-03-01 09:00", "disk:sda"),
+            Some(usage(7, 0))
+        );
+        assert_eq!(
+            bucket(&records.hours, "2025-03-01 10:00", "net:eth0"),
+            Some(usage(4, 40))
+        );
+    }
+
+    #[test]
+    fn daily_and_monthly_rollups_sum_across_boundaries() {
+        let mut records = Records::default();
+        records.add(
+            at(2025, Month::January, 31, 23, 59),
+            increments(&[("net:eth0", usage(1, 1))]),
+        );
+        records.add(
+            at(2025, Month::February, 1, 0, 0),
+            increments(&[("net:eth0", usage(2, 2))]),
+        );
+        records.add(
+            at(2025, Month::February, 28, 12, 0),
+            increments(&[("net:eth0", usage(4, 4))]),
+        );
+
+        assert_eq!(
+            bucket(&records.days, "2025-01-31", "net:eth0"),
+            Some(usage(1, 1))
+        );
+        assert_eq!(
+            bucket(&records.days, "2025-02-01", "net:eth0"),
+            Some(usage(2, 2))
+        );
+        assert_eq!(
+            bucket(&records.months, "2025-01", "net:eth0"),
+            Some(usage(1, 1))
+        );
+        assert_eq!(
+            bucket(&records.months, "2025-02", "net:eth0"),
+            Some(usage(6, 6))
+        );
+    }
+
+    #[test]
+    fn zero_increments_create_no_buckets() {
+        let mut records = Records::default();
+        records.add(
+            at(2025, Month::March, 1, 9, 0),
+            increments(&[("net:eth0", Usage::default())]),
+        );
+        assert!(records.hours.is_empty() && records.days.is_empty() && records.months.is_empty());
+    }
+
+    #[test]
+    fn rollups_keep_latest_buckets() {
+        let mut records = Records::default();
+        let start = at(2025, Month::January, 1, 0, 0);
+        for hour in 0..KEPT_HOURS as u64 + 5 {
+            records.add(
+                start + std::time::Duration::from_secs(hour * 3600),
+                increments(&[("net:eth0", usage(1, 1))]),
+            );
+        }
+        assert_eq!(records.hours.len(), KEPT_HOURS);
+        assert_eq!(
+            records.hours.keys().next().map(String::as_str),
+            Some("2025-01-01 05:00")
+        );
+        assert_eq!(
+            bucket(&records.months, "2025-01", "net:eth0"),
+            Some(usage(KEPT_HOURS as u64 + 5, KEPT_HOURS as u64 + 5))
+        );
+    }
+
+    #[test]
+    fn content_round_trips_through_parse() {
+        let mut records = Records::default();
+        records.add(
+            at(2025, Month::March, 1, 9, 0),
+            increments(&[("net:eth0", usage(1, 2)), ("disk:sda", usage(3, 4))]),
+        );
+        let boot = Boot {
+            id: Some("3f2a".to_string()),
+            time: Some(42),
+        };
+        let mut counters = Counters::new(boot.clone());
+        counters.update([("net:eth0", usage(100, 200))]);
+
+        let parsed = Records::parse(&records.content(&counters));
+        assert_eq!(parsed.boot, boot);
+        assert_eq!(parsed.counters["net:eth0"], usage(100, 200));
+        assert_eq!(parsed.hours, records.hours);
+        assert_eq!(parsed.days, records.days);
+        assert_eq!(parsed.months, records.months);
+    }
+}
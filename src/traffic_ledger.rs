@@ -0,0 +1,312 @@
+use crate::command_parser::Args;
+use crate::data_struct::Traffic;
+use crate::traffic_store::{Boot, Counters, StoreFile, Usage, trim};
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use sysinfo::NetworkData;
+use time::{Date, Month, OffsetDateTime, UtcOffset};
+
+/// 保留的每日记录天数与计费周期数
+const KEPT_DAYS: usize = 62;
+const KEPT_CYCLES: usize = 24;
+
+/// 持久化的流量账本
+///
+/// 记录每块网卡上次看到的累计计数，按差值累加到当天与当前计费周期；计数变小 (重启、计数器回绕)
+/// 时把当前计数视为增量，首次看到的网卡只记录基准。文件为制表符分隔的文本，每行一条记录
+#[derive(Debug)]
+pub struct TrafficLedger {
+    file: StoreFile,
+    reset_day: u8,
+    offset: UtcOffset,
+    counters: Counters,
+    days: BTreeMap<String, Usage>,
+    cycles: BTreeMap<String, Usage>,
+}
+
+impl TrafficLedger {
+    pub fn new(args: &Args, path: &str) -> Self {
+        // 多线程环境下 now_local 可能失败，启动时确定一次时区偏移，避免日期在本地时间与 UTC 间跳动
+        let offset = OffsetDateTime::now_local().map_or(UtcOffset::UTC, OffsetDateTime::offset);
+        let mut ledger = Self {
+            file: StoreFile::new(path, "流量账本"),
+            reset_day: args.traffic_reset_day,
+            offset,
+            counters: Counters::new(Boot::current()),
+            days: BTreeMap::new(),
+            cycles: BTreeMap::new(),
+        };
+        ledger.load();
+        info!(
+            "流量账本: {}，每月 {} 日重置，时区偏移 {offset}",
+            ledger.file.path().display(),
+            ledger.reset_day
+        );
+        ledger
+    }
+
+    pub fn reconfigure(&mut self, args: &Args) {
+        self.reset_day = args.traffic_reset_day;
+    }
+
+    fn load(&mut self) {
+        let content = match self.file.read() {
+            Ok(Some(content)) => content,
+            Ok(None) => return,
+            Err(e) => {
+                error!("{e}");
+                return;
+            }
+        };
+
+        let mut saved_boot = Boot::default();
+        let mut counters = HashMap::new();
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                [key @ ("boot_id" | "boot_time"), value] => {
+                    if !saved_boot.parse(key, value) {
+                        warn!("流量账本中存在无效的记录: {line}");
+                    }
+                }
+                [kind, key, up, down] => {
+                    let Some(usage) = Usage::parse(up, down) else {
+                        warn!("流量账本中存在无效的记录: {line}");
+                        continue;
+                    };
+                    match *kind {
+                        "counter" => {
+                            counters.insert((*key).to_string(), usage);
+                        }
+                        "day" => {
+                            self.days.insert((*key).to_string(), usage);
+                        }
+                        "cycle" => {
+                            self.cycles.insert((*key).to_string(), usage);
+                        }
+                        _ => warn!("流量账本中存在无效的记录: {line}"),
+                    }
+                }
+                _ => warn!("流量账本中存在无效的记录: {line}"),
+            }
+        }
+
+        if !self.counters.restore(&saved_boot, counters) {
+            info!("系统已重启，流量账本将从网卡当前计数开始累计");
+        }
+    }
+
+    /// 累加本周期的流量，并按间隔写入磁盘
+    pub fn record(&mut self, selected: &[(&str, &NetworkData)]) {
+        let mut delta = Usage::default();
+        for (_, increment) in self.counters.update(selected.iter().map(|(name, data)| {
+            let usage = Usage {
+                up: data.total_transmitted(),
+                down: data.total_received(),
+            };
+            (*name, usage)
+        })) {
+            delta.add(increment);
+        }
+        self.add(self.today(), delta);
+
+        self.file
+            .changed(|| content(&self.counters, &self.days, &self.cycles));
+    }
+
+    /// 把增量计入指定日期与其所在的计费周期
+    fn add(&mut self, today: Date, delta: Usage) {
+        if delta == Usage::default() {
+            return;
+        }
+        self.days.entry(today.to_string()).or_default().add(delta);
+        self.cycles
+            .entry(cycle_start(today, self.reset_day).to_string())
+            .or_default()
+            .add(delta);
+        trim(&mut self.days, KEPT_DAYS);
+        trim(&mut self.cycles, KEPT_CYCLES);
+    }
+
+    /// 当前计费周期与当天的用量
+    pub fn usage(&self) -> Traffic {
+        self.usage_on(self.today())
+    }
+
+    fn usage_on(&self, today: Date) -> Traffic {
+        let cycle_start = cycle_start(today, self.reset_day).to_string();
+        let cycle = self.cycles.get(&cycle_start).copied().unwrap_or_default();
+        let day = self
+            .days
+            .get(&today.to_string())
+            .copied()
+            .unwrap_or_default();
+
+        Traffic {
+            cycle_start,
+            cycle_up: cycle.up,
+            cycle_down: cycle.down,
+            today_up: day.up,
+            today_down: day.down,
+        }
+    }
+
+    pub fn save(&mut self) {
+        self.file
+            .save(|| content(&self.counters, &self.days, &self.cycles));
+    }
+
+    fn today(&self) -> Date {
+        OffsetDateTime::now_utc().to_offset(self.offset).date()
+    }
+}
+
+fn content(
+    counters: &Counters,
+    days: &BTreeMap<String, Usage>,
+    cycles: &BTreeMap<String, Usage>,
+) -> String {
+    let mut content = String::new();
+    counters.write(&mut content);
+    for (date, usage) in days {
+        let _ = writeln!(content, "day\t{date}\t{}\t{}", usage.up, usage.down);
+    }
+    for (date, usage) in cycles {
+        let _ = writeln!(content, "cycle\t{date}\t{}\t{}", usage.up, usage.down);
+    }
+    content
+}
+
+/// 计费周期的起始日期，重置日超过当月天数时取当月最后一天
+fn cycle_start(today: Date, reset_day: u8) -> Date {
+    let reset_date = |year: i32, month: Month| {
+        let day = reset_day.clamp(1, time::util::days_in_month(month, year));
+        Date::from_calendar_date(year, month, day).unwrap_or(today)
+    };
+
+    let this_month = reset_date(today.year(), today.month());
+    if today >= this_month {
+        return this_month;
+    }
+
+    match today.month() {
+        Month::January => reset_date(today.year() - 1, Month::December),
+        month => reset_date(today.year(), month.previous()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn usage(up: u64, down: u64) -> Usage {
+        Usage { up, down }
+    }
+
+    fn ledger(dir: &tempfile::TempDir, reset_day: &str) -> TrafficLedger {
+        let args = Args::try_parse_from([
+            "komari-monitor-rs",
+            "--http-server",
+            "http://panel",
+            "-t",
+            "t",
+            "--traffic-reset-day",
+            reset_day,
+        ])
+        .unwrap();
+        TrafficLedger::new(&args, dir.path().join("ledger").to_str().unwrap())
+    }
+
+    #[test]
+    fn cycle_start_clamps_reset_day_to_month_end() {
+        assert_eq!(
+            cycle_start(date(2025, Month::February, 28), 31),
+            date(2025, Month::February, 28)
+        );
+        assert_eq!(
+            cycle_start(date(2024, Month::February, 28), 31),
+            date(2024, Month::January, 31)
+        );
+        assert_eq!(
+            cycle_start(date(2024, Month::February, 29), 31),
+            date(2024, Month::February, 29)
+        );
+        assert_eq!(
+            cycle_start(date(2025, Month::April, 30), 31),
+            date(2025, Month::April, 30)
+        );
+        assert_eq!(
+            cycle_start(date(2025, Month::May, 15), 31),
+            date(2025, Month::April, 30)
+        );
+    }
+
+    #[test]
+    fn cycle_start_before_reset_day_uses_previous_month() {
+        assert_eq!(
+            cycle_start(date(2025, Month::March, 14), 15),
+            date(2025, Month::February, 15)
+        );
+        assert_eq!(
+            cycle_start(date(2025, Month::March, 15), 15),
+            date(2025, Month::March, 15)
+        );
+        assert_eq!(
+            cycle_start(date(2025, Month::January, 3), 5),
+            date(2024, Month::December, 5)
+        );
+        assert_eq!(
+            cycle_start(date(2025, Month::January, 1), 1),
+            date(2025, Month::January, 1)
+        );
+    }
+
+    #[test]
+    fn usage_rolls_days_into_billing_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = ledger(&dir, "15");
+        ledger.add(date(2025, Month::March, 13), usage(1, 10));
+        ledger.add(date(2025, Month::March, 14), usage(2, 20));
+        ledger.add(date(2025, Month::March, 15), usage(4, 40));
+        ledger.add(date(2025, Month::March, 15), usage(8, 80));
+
+        let before_reset = ledger.usage_on(date(2025, Month::March, 14));
+        assert_eq!(before_reset.cycle_start, "2025-02-15");
+        assert_eq!((before_reset.cycle_up, before_reset.cycle_down), (3, 30));
+        assert_eq!((before_reset.today_up, before_reset.today_down), (2, 20));
+
+        let after_reset = ledger.usage_on(date(2025, Month::March, 15));
+        assert_eq!(after_reset.cycle_start, "2025-03-15");
+        assert_eq!((after_reset.cycle_up, after_reset.cycle_down), (12, 120));
+        assert_eq!((after_reset.today_up, after_reset.today_down), (12, 120));
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = self::ledger(&dir, "1");
+        ledger.add(date(2025, Month::March, 2), usage(5, 6));
+        ledger.counters.update([("eth0", usage(100, 200))]);
+        ledger.file.changed(String::new);
+        ledger.save();
+
+        let content = fs::read_to_string(dir.path().join("ledger")).unwrap();
+        assert!(content.contains("counter\teth0\t100\t200\n"));
+        assert!(content.ends_with("day\t2025-03-02\t5\t6\ncycle\t2025-03-01\t5\t6\n"));
+
+        let mut ledger = self::ledger(&dir, "1");
+        assert_eq!(ledger.days["2025-03-02"], usage(5, 6));
+        assert_eq!(ledger.cycles["2025-03-01"], usage(5, 6));
+        assert_eq!(
+            ledger.counters.update([("eth0", usage(150, 260))]),
+            [("eth0", usage(50, 60))]
+        );
+    }
+}
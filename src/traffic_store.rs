@@ -0,0 +1,434 @@
+use log::{debug, error};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::System;
+
+/// 流量账本与流量历史写入磁盘的间隔，退出时也会写入一次
+const SAVE_INTERVAL: Duration = Duration::from_mins(1);
+
+/// 开机时间允许的误差 (s)，超过即认为系统已重启
+const BOOT_TIME_TOLERANCE: u64 = 10;
+
+/// 每次开机随机生成，不受系统时间调整的影响
+const BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub up: u64,
+    pub down: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: Usage) {
+        self.up = self.up.saturating_add(other.up);
+        self.down = self.down.saturating_add(other.down);
+    }
+
+    pub fn parse(up: &str, down: &str) -> Option<Self> {
+        Some(Self {
+            up: up.parse().ok()?,
+            down: down.parse().ok()?,
+        })
+    }
+}
+
+/// 用于判断系统是否已重启的开机标识，以文件中的 `boot_id` 与 `boot_time` 记录持久化
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Boot {
+    /// Linux 的 `boot_id`，NTP 校时等导致的开机时间变化不会被误判为重启
+    pub id: Option<String>,
+    /// 开机时间 (s)，仅在其他平台或旧文件中没有 `boot_id` 时使用
+    pub time: Option<u64>,
+}
+
+impl Boot {
+    pub fn current() -> Self {
+        let id = fs::read_to_string(BOOT_ID_PATH)
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty());
+        Self {
+            id,
+            time: Some(System::boot_time()),
+        }
+    }
+
+    /// 解析 `boot_id` 或 `boot_time` 记录，值无效时返回 false
+    pub fn parse(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "boot_id" if !value.is_empty() => self.id = Some(value.to_string()),
+            "boot_time" => match value.parse() {
+                Ok(time) => self.time = Some(time),
+                Err(_) => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_same(&self, saved: &Boot) -> bool {
+        match (&self.id, &saved.id) {
+            (Some(current), Some(saved)) => current == saved,
+            _ => matches!(
+                (self.time, saved.time),
+                (Some(current), Some(saved)) if current.abs_diff(saved) <= BOOT_TIME_TOLERANCE
+            ),
+        }
+    }
+
+    fn write(&self, content: &mut String) {
+        if let Some(id) = &self.id {
+            let _ = writeln!(content, "boot_id\t{id}");
+        }
+        if let Some(time) = self.time {
+            let _ = writeln!(content, "boot_time\t{time}");
+        }
+    }
+}
+
+/// 每个序列 (网卡或磁盘) 上次看到的累计计数
+///
+/// 以文件中的开机标识与 `counter` 记录持久化，系统重启后丢弃旧的计数
+#[derive(Debug)]
+pub struct Counters {
+    boot: Boot,
+    previous: HashMap<String, Usage>,
+    /// 系统重启后的首次记录中，计数均产生于开机之后，全部计入用量
+    count_since_boot: bool,
+}
+
+impl Counters {
+    pub fn new(boot: Boot) -> Self {
+        Self {
+            boot,
+            previous: HashMap::new(),
+            count_since_boot: false,
+        }
+    }
+
+    /// 恢复文件中保存的计数，系统已重启时返回 false
+    pub fn restore(&mut self, saved_boot: &Boot, saved: HashMap<String, Usage>) -> bool {
+        // 重启后计数从 0 开始，旧的计数没有比较意义
+        if self.boot.is_same(saved_boot) {
+            self.previous = saved;
+            true
+        } else {
+            self.count_since_boot = true;
+            false
+        }
+    }
+
+    /// 用本周期的计数更新基准，返回每个序列相对上一周期的增量
+    ///
+    /// 首次看到的序列 (新出现或刚被选中) 只记录基准，避免把开机以来的累计计数算入；
+    /// 本周期未出现的序列不再保留基准，再次出现时重新开始
+    pub fn update<K: AsRef<str>>(
+        &mut self,
+        current: impl IntoIterator<Item = (K, Usage)>,
+    ) -> Vec<(K, Usage)> {
+        let mut counters = HashMap::with_capacity(self.previous.len());
+        let increments = current
+            .into_iter()
+            .map(|(series, current)| {
+                let increment = match self.previous.get(series.as_ref()) {
+                    Some(previous) => Usage {
+                        up: counter_delta(previous.up, current.up),
+                        down: counter_delta(previous.down, current.down),
+                    },
+                    None if self.count_since_boot => current,
+                    None => Usage::default(),
+                };
+                counters.insert(series.as_ref().to_string(), current);
+                (series, increment)
+            })
+            .collect();
+        self.previous = counters;
+        self.count_since_boot = false;
+        increments
+    }
+
+    /// 写入开机标识与按名称排序的 `counter` 记录
+    pub fn write(&self, content: &mut String) {
+        self.boot.write(content);
+        let mut counters: Vec<_> = self.previous.iter().collect();
+        counters.sort_by_key(|(series, _)| *series);
+        for (series, usage) in counters {
+            let _ = writeln!(content, "counter\t{series}\t{}\t{}", usage.up, usage.down);
+        }
+    }
+}
+
+/// 计数变小说明网卡被重置或计数器回绕，此时当前计数即为增量
+fn counter_delta(previous: u64, current: u64) -> u64 {
+    if current >= previous {
+        current - previous
+    } else {
+        current
+    }
+}
+
+/// 制表符分隔的记录文件，按间隔写入磁盘
+#[derive(Debug)]
+pub struct StoreFile {
+    path: PathBuf,
+    /// 日志中的文件描述，如 "流量账本"
+    description: &'static str,
+    last_saved: Instant,
+    dirty: bool,
+}
+
+impl StoreFile {
+    pub fn new(path: &str, description: &'static str) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            description,
+            last_saved: Instant::now(),
+            dirty: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 文件不存在时返回 None
+    pub fn read(&self) -> Result<Option<String>, String> {
+        read_file(&self.path, self.description)
+    }
+
+    /// 标记内容已变化，并在距上次写入超过间隔时写入
+    pub fn changed(&mut self, content: impl FnOnce() -> String) {
+        self.dirty = true;
+        if self.last_saved.elapsed() >= SAVE_INTERVAL {
+            self.save(content);
+        }
+    }
+
+    pub fn save(&mut self, content: impl FnOnce() -> String) {
+        self.last_saved = Instant::now();
+        if !self.dirty {
+            return;
+        }
+
+        // 先写入临时文件再重命名，避免写入中途断电导致文件损坏
+        let tmp_path = self.path.with_extension("tmp");
+        match fs::write(&tmp_path, content()).and_then(|()| fs::rename(&tmp_path, &self.path)) {
+            Ok(()) => {
+                self.dirty = false;
+                debug!("{}已写入 {}", self.description, self.path.display());
+            }
+            Err(e) => error!("无法写入{} {}: {e}", self.description, self.path.display()),
+        }
+    }
+}
+
+/// 文件不存在时返回 None
+pub fn read_file(path: &Path, description: &str) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("无法读取{description} {}: {e}", path.display())),
+    }
+}
+
+/// 只保留最近的 `keep` 个时间段
+pub fn trim<V>(entries: &mut BTreeMap<String, V>, keep: usize) {
+    while entries.len() > keep {
+        entries.pop_first();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(up: u64, down: u64) -> Usage {
+        Usage { up, down }
+    }
+
+    fn boot(id: Option<&str>, time: u64) -> Boot {
+        Boot {
+            id: id.map(ToString::to_string),
+            time: Some(time),
+        }
+    }
+
+    fn increments(counters: &mut Counters, current: &[(&'static str, Usage)]) -> Vec<Usage> {
+        counters
+            .update(current.iter().copied())
+            .into_iter()
+            .map(|(_, usage)| usage)
+            .collect()
+    }
+
+    #[test]
+    fn counter_delta_treats_decrease_as_reset() {
+        assert_eq!(counter_delta(100, 150), 50);
+        assert_eq!(counter_delta(100, 100), 0);
+        assert_eq!(counter_delta(u64::MAX - 5, 20), 20);
+        assert_eq!(counter_delta(5000, 0), 0);
+    }
+
+    #[test]
+    fn first_sight_only_seeds_baseline() {
+        let mut counters = Counters::new(boot(None, 1000));
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(1000, 5000))]),
+            [Usage::default()]
+        );
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(1100, 5500))]),
+            [usage(100, 500)]
+        );
+
+        // 运行中新选中的网卡同样只记录基准
+        assert_eq!(
+            increments(
+                &mut counters,
+                &[("eth0", usage(1200, 5500)), ("eth1", usage(9000, 9000))]
+            ),
+            [usage(100, 0), Usage::default()]
+        );
+        assert_eq!(
+            increments(
+                &mut counters,
+                &[("eth0", usage(1200, 5500)), ("eth1", usage(9010, 9020))]
+            ),
+            [Usage::default(), usage(10, 20)]
+        );
+    }
+
+    #[test]
+    fn missing_series_is_seeded_again_when_it_returns() {
+        let mut counters = Counters::new(boot(None, 1000));
+        increments(
+            &mut counters,
+            &[("eth0", usage(0, 0)), ("eth1", usage(10, 10))],
+        );
+        increments(&mut counters, &[("eth0", usage(5, 5))]);
+        assert_eq!(
+            increments(
+                &mut counters,
+                &[("eth0", usage(5, 5)), ("eth1", usage(900, 900))]
+            ),
+            [Usage::default(), Usage::default()]
+        );
+    }
+
+    #[test]
+    fn counter_reset_counts_current_value() {
+        let mut counters = Counters::new(boot(None, 1000));
+        increments(&mut counters, &[("eth0", usage(1000, 1000))]);
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(30, 40))]),
+            [usage(30, 40)]
+        );
+    }
+
+    #[test]
+    fn restore_keeps_counters_within_boot_time_tolerance() {
+        let saved = HashMap::from([("eth0".to_string(), usage(100, 200))]);
+        let mut counters = Counters::new(boot(None, 1000));
+        assert!(counters.restore(&boot(None, 1000 - BOOT_TIME_TOLERANCE), saved));
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(150, 260))]),
+            [usage(50, 60)]
+        );
+    }
+
+    #[test]
+    fn boot_id_decides_when_both_sides_have_it() {
+        let saved = HashMap::from([("eth0".to_string(), usage(100, 200))]);
+
+        // NTP 校时使开机时间大幅变化，但仍是同一次开机
+        let mut counters = Counters::new(boot(Some("a"), 5000));
+        assert!(counters.restore(&boot(Some("a"), 1000), saved.clone()));
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(150, 260))]),
+            [usage(50, 60)]
+        );
+
+        let mut counters = Counters::new(boot(Some("b"), 1000));
+        assert!(!counters.restore(&boot(Some("a"), 1000), saved.clone()));
+
+        // 旧文件中没有 boot_id 时退回到开机时间
+        let mut counters = Counters::new(boot(Some("a"), 1000));
+        assert!(counters.restore(&boot(None, 1001), saved.clone()));
+        let mut counters = Counters::new(boot(Some("a"), 1000));
+        assert!(!counters.restore(&Boot::default(), saved));
+    }
+
+    #[test]
+    fn first_update_after_reboot_counts_since_boot() {
+        let saved = HashMap::from([("eth0".to_string(), usage(999_999, 999_999))]);
+        let mut counters = Counters::new(boot(None, 1000));
+        assert!(!counters.restore(&boot(None, 1000 + BOOT_TIME_TOLERANCE + 1), saved));
+        assert_eq!(
+            increments(&mut counters, &[("eth0", usage(300, 400))]),
+            [usage(300, 400)]
+        );
+        assert_eq!(
+            increments(
+                &mut counters,
+                &[("eth0", usage(310, 400)), ("eth1", usage(50, 50))]
+            ),
+            [usage(10, 0), Usage::default()]
+        );
+    }
+
+    #[test]
+    fn write_is_sorted_and_round_trips() {
+        let mut counters = Counters::new(boot(Some("3f2a"), 42));
+        increments(
+            &mut counters,
+            &[("wlan0", usage(3, 4)), ("eth0", usage(1, 2))],
+        );
+        let mut content = String::new();
+        counters.write(&mut content);
+        assert_eq!(
+            content,
+            "boot_id\t3f2a\nboot_time\t42\ncounter\teth0\t1\t2\ncounter\twlan0\t3\t4\n"
+        );
+
+        let mut parsed = Boot::default();
+        assert!(parsed.parse("boot_id", "3f2a"));
+        assert!(parsed.parse("boot_time", "42"));
+        assert!(!parsed.parse("boot_time", "x"));
+        assert!(!parsed.parse("boot_id", ""));
+        assert_eq!(parsed, boot(Some("3f2a"), 42));
+        assert_eq!(Usage::parse("1", "2"), Some(usage(1, 2)));
+        assert_eq!(Usage::parse("1", "x"), None);
+    }
+
+    #[test]
+    fn store_file_saves_atomically_only_when_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store");
+        let mut file = StoreFile::new(path.to_str().unwrap(), "测试文件");
+        assert_eq!(file.read(), Ok(None));
+
+        file.save(|| unreachable!("未变化时不应写入"));
+        assert!(!path.exists());
+
+        file.changed(|| unreachable!("未到写入间隔"));
+        file.save(|| "a\t1\n".to_string());
+        assert_eq!(file.read(), Ok(Some("a\t1\n".to_string())));
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn trim_keeps_latest_entries() {
+        let mut entries: BTreeMap<String, Usage> = ["2025-01", "2025-03", "2025-02"]
+            .into_iter()
+            .map(|key| (key.to_string(), Usage::default()))
+            .collect();
+        trim(&mut entries, 2);
+        assert_eq!(
+            entries.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["2025-02", "2025-03"]
+        );
+    }
+}
@@ -1,12 +1,20 @@
-use crate::command_parser::LogLevel;
-use crate::rustls_config::create_dangerous_config;
+use crate::auth::{PanelAuth, Secret};
+use crate::command_parser::{AuthMode, LogLevel};
+use crate::proxy::proxy_for;
+use crate::rustls_config::panel_tls_config;
 use log::{Level, info};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::UrlError;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::http::header::AUTHORIZATION;
 use tokio_tungstenite::{
-    Connector, MaybeTlsStream, WebSocketStream, connect_async, connect_async_tls_with_config,
+    Connector, MaybeTlsStream, WebSocketStream, client_async_tls_with_config,
+    connect_async_tls_with_config,
 };
 use url::{ParseError, Url};
 
@@ -14,13 +22,9 @@ pub fn init_logger(log_level: &LogLevel) {
     #[cfg(target_os = "windows")]
     simple_logger::set_up_windows_color_terminal();
 
-    match log_level {
-        LogLevel::Error => simple_logger::init_with_level(Level::Error).unwrap(),
-        LogLevel::Warn => simple_logger::init_with_level(Level::Warn).unwrap(),
-        LogLevel::Info => simple_logger::init_with_level(Level::Info).unwrap(),
-        LogLevel::Debug => simple_logger::init_with_level(Level::Debug).unwrap(),
-        LogLevel::Trace => simple_logger::init_with_level(Level::Trace).unwrap(),
-    }
+    // 以 Trace 初始化，实际等级由 log::set_max_level 控制，便于热重载时调整
+    simple_logger::init_with_level(Level::Trace).unwrap();
+    log::set_max_level(log_level.to_level_filter());
 }
 
 #[derive(Debug, Clone)]
@@ -29,12 +33,30 @@ pub struct ConnectionUrls {
     pub exec_callback: String,
     pub ws_terminal: String,
     pub ws_real_time: String,
+    pub auth: Arc<PanelAuth>,
+}
+
+/// 为每个主端构造一组 URL，`ws_servers` 按顺序与 `http_servers` 对应，各地址的鉴权状态相互独立
+pub fn build_server_list(
+    http_servers: &[String],
+    ws_servers: &[String],
+    token: &Secret,
+    auth_mode: &AuthMode,
+) -> Result<Vec<ConnectionUrls>, ParseError> {
+    http_servers
+        .iter()
+        .enumerate()
+        .map(|(index, http_server)| {
+            let auth = Arc::new(PanelAuth::new(token, auth_mode));
+            build_urls(http_server, ws_servers.get(index), auth)
+        })
+        .collect()
 }
 
-pub fn build_urls(
+fn build_urls(
     http_server: &str,
     ws_server: Option<&String>,
-    token: &str,
+    auth: Arc<PanelAuth>,
 ) -> Result<ConnectionUrls, ParseError> {
     // 1. 构造 http_url_base
     let http_url = Url::parse(http_server)?;
@@ -54,17 +76,18 @@ pub fn build_urls(
     };
     let ws_url_base = ws_url.as_str().trim_end_matches('/').to_string();
 
-    // 3. 构造各个最终 URL
-    let basic_info_url = format!("{http_url_base}/api/clients/uploadBasicInfo?token={token}");
-    let exec_callback_url = format!("{http_url_base}/api/clients/task/result?token={token}");
-    let ws_terminal_url = format!("{ws_url_base}/api/clients/terminal?token={token}");
-    let ws_real_time_url = format!("{ws_url_base}/api/clients/report?token={token}");
+    // 3. 构造各个最终 URL，Token 在发起请求时由 auth 模块附加
+    let basic_info_url = format!("{http_url_base}/api/clients/uploadBasicInfo");
+    let exec_callback_url = format!("{http_url_base}/api/clients/task/result");
+    let ws_terminal_url = format!("{ws_url_base}/api/clients/terminal");
+    let ws_real_time_url = format!("{ws_url_base}/api/clients/report");
 
     let connection_urls = ConnectionUrls {
         basic_info: basic_info_url,
         exec_callback: exec_callback_url,
         ws_terminal: ws_terminal_url,
         ws_real_time: ws_real_time_url,
+        auth,
     };
 
     info!("URL 解析成功: {connection_urls:?}");
@@ -74,56 +97,143 @@ pub fn build_urls(
 
 pub async fn connect_ws(
     url: &str,
-    tls: bool,
-    skip_verify: bool,
+    auth: &PanelAuth,
 ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, String> {
     let connection_timeout = Duration::from_secs(10);
 
-    if tls {
-        if skip_verify {
-            timeout(
-                connection_timeout,
-                connect_async_tls_with_config(
-                    url,
-                    None,
-                    false,
-                    Some(Connector::Rustls(Arc::new(create_dangerous_config()))),
-                ),
-            )
-            .await
-            .map_err(|_| "WebSocket 连接超时".to_string())?
-            .map(|ws| ws.0)
-            .map_err(|_| "无法创立 WebSocket 连接".to_string())
-        } else {
-            timeout(
-                connection_timeout,
-                connect_async_tls_with_config(url, None, false, None),
-            )
+    timeout(connection_timeout, async {
+        match connect_ws_with_auth(url, auth).await {
+            Err(WsError::Http(response))
+                if auth.fallback_to_query(url, response.status().as_u16()) =>
+            {
+                connect_ws_with_auth(url, auth).await
+            }
+            other => other,
+        }
+        .inspect(|_| auth.accepted())
+        .map_err(|e| match e {
+            WsError::Http(response) => {
+                format!(
+                    "无法创立 WebSocket 连接，HTTP 状态码: {}",
+                    response.status()
+                )
+            }
+            e => format!("无法创立 WebSocket 连接: {e}"),
+        })
+    })
+    .await
+    .map_err(|_| "WebSocket 连接超时".to_string())?
+}
+
+async fn connect_ws_with_auth(
+    url: &str,
+    auth: &PanelAuth,
+) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, WsError> {
+    let connector = Some(Connector::Rustls(panel_tls_config()));
+
+    let mut request = auth.authorize_url(url).into_client_request()?;
+    if let Some(value) = auth.authorization_header() {
+        let value = HeaderValue::from_str(&value).map_err(|e| WsError::HttpFormat(e.into()))?;
+        request.headers_mut().insert(AUTHORIZATION, value);
+    }
+
+    if let Some(proxy) = proxy_for(url) {
+        let uri = request.uri();
+        let host = uri.host().ok_or(WsError::Url(UrlError::NoHostName))?;
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("wss") {
+                443
+            } else {
+                80
+            });
+
+        let stream = proxy.connect(host, port).await.map_err(WsError::Io)?;
+
+        client_async_tls_with_config(request, stream, None, connector)
             .await
-            .map_err(|_| "WebSocket 连接超时".to_string())?
             .map(|ws| ws.0)
-            .map_err(|_| "无法创立 WebSocket 连接".into())
-        }
     } else {
-        timeout(connection_timeout, connect_async(url))
+        connect_async_tls_with_config(request, None, false, connector)
             .await
-            .map_err(|_| "WebSocket 连接超时".to_string())?
             .map(|ws| ws.0)
-            .map_err(|_| "无法创立 WebSocket 连接".to_string())
     }
 }
 
+/// 向主端 POST JSON，按鉴权方式附加 Token，返回 HTTP 状态码
+pub fn post_to_panel(url: &str, json_string: &str, auth: &PanelAuth) -> Result<u16, String> {
+    let mut status = post_to_panel_with_auth(url, json_string, auth)?;
+    if auth.fallback_to_query(url, status) {
+        status = post_to_panel_with_auth(url, json_string, auth)?;
+    }
+    if (200..300).contains(&status) {
+        auth.accepted();
+    }
+    Ok(status)
+}
+
 #[cfg(feature = "ureq-support")]
-pub fn create_ureq_agent(disable_verification: bool) -> ureq::Agent {
+fn post_to_panel_with_auth(url: &str, json_string: &str, auth: &PanelAuth) -> Result<u16, String> {
+    use crate::rustls_config::panel_tls_config;
+
+    let agent = create_ureq_agent(Some(panel_tls_config()));
+    let mut request = agent
+        .post(auth.authorize_url(url))
+        .header("User-Agent", "curl/11.45.14-rs")
+        .header("Content-Type", "application/json");
+    if let Some(value) = auth.authorization_header() {
+        request = request.header("Authorization", value);
+    }
+
+    match request.send(json_string) {
+        Ok(resp) => Ok(resp.status().as_u16()),
+        Err(ureq::Error::StatusCode(status)) => Ok(status),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(feature = "nyquest-support")]
+fn post_to_panel_with_auth(url: &str, json_string: &str, auth: &PanelAuth) -> Result<u16, String> {
+    use nyquest::{Body, Request};
+
+    let client = create_nyquest_client(crate::rustls_config::ignore_unsafe_cert());
+    let body = Body::text(json_string.to_string(), "application/json");
+    let mut request = Request::post(auth.authorize_url(url)).with_body(body);
+    if let Some(value) = auth.authorization_header() {
+        request = request.with_header("Authorization", value);
+    }
+
+    client
+        .request(request)
+        .map(|resp| resp.status().code())
+        .map_err(|e| e.to_string())
+}
+
+/// 创建 ureq Agent，访问主端时传入 `panel_tls_config()`，访问公共接口时传入 None
+#[cfg(feature = "ureq-support")]
+pub fn create_ureq_agent(tls_config: Option<Arc<rustls::ClientConfig>>) -> ureq::Agent {
+    use crate::rustls_config::ureq_connector::CustomRustlsConnector;
+    use ureq::unversioned::resolver::DefaultResolver;
+    use ureq::unversioned::transport::{
+        ConnectProxyConnector, Connector, SocksConnector, TcpConnector,
+    };
+
     let config = ureq::Agent::config_builder()
-        .tls_config(
-            ureq::tls::TlsConfig::builder()
-                .disable_verification(disable_verification)
-                .build(),
-        )
         .timeout_global(Some(Duration::from_secs(5)))
+        .proxy(crate::proxy::global().and_then(crate::proxy::ProxyConfig::to_ureq))
         .build();
-    config.new_agent()
+
+    match tls_config {
+        Some(tls_config) => {
+            let connector =
+                ().chain(SocksConnector::default())
+                    .chain(ConnectProxyConnector::default())
+                    .chain(TcpConnector::default())
+                    .chain(CustomRustlsConnector::new(tls_config));
+            ureq::Agent::with_parts(config, connector, DefaultResolver::default())
+        }
+        None => config.new_agent(),
+    }
 }
 
 #[cfg(feature = "nyquest-support")]